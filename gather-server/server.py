@@ -1,52 +1,264 @@
 #!/usr/bin/env python3
 
+import hashlib
+import os
+
 from aiohttp import web, web_request
 
 valid_client_keys = [
     "test_key",
 ]
 
-seeds_to_search = [f"test{x}" for x in range(500)]
+# Every seed is searched once on each of these. None means whatever the client runs by default.
+minecraft_versions = [None]
+
+seeds_to_search = [
+    (f"test{x}", version) for x in range(500) for version in minecraft_versions
+]
 
+submission_counts = {}
 
-async def assign_handler(request: web_request.Request):
-    global valid_client_keys
+# Files in here are offered to clients as their server_template
+template_dir = "template"
+
+# The newest client release, offered to clients checking for updates. Something like:
+# {"version": "0.2.0", "notes": "...", "downloads": {"linux-x86_64": {"url": ..., "sha256": ..., "signature": ...}}}
+# where the signature is a hex ed25519 signature of the version's UTF-8 bytes followed by the
+# binary's raw 32-byte SHA-256, so a release can't be replayed as a different version.
+latest_client = None
+
+# Clients older than this are refused at handshake
+minimum_client_version = (0, 1, 0)
+
+# Result formats this server can ingest, most preferred first. "summary" is a
+# JSON summary of the raw result, and "raw+summary" is a JSON object with both.
+accepted_result_formats = ["raw+summary", "raw", "summary"]
+
+# Clients send a PNG biome preview of each seed they submit if this is set, and they're kept in here
+preview_dir = "previews"
+accept_previews = True
 
-    if "client_key" not in request.match_info:
+
+def get_client_key(request: web_request.Request):
+    if "client_key" in request.match_info:
+        return request.match_info["client_key"]
+
+    authorization = request.headers.get("Authorization", "")
+    if authorization.startswith("Bearer "):
+        return authorization[len("Bearer "):]
+
+    return None
+
+
+def check_client_key(request: web_request.Request):
+    client_key = get_client_key(request)
+
+    if client_key is None:
         return web.Response(status=401, text="Missing client key")
 
-    if request.match_info["client_key"] not in valid_client_keys:
+    if client_key not in valid_client_keys:
         return web.Response(status=401, text="Invalid client key")
 
+    return None
+
+
+async def health_handler(request: web_request.Request):
+    return web.Response(status=200, text="OK")
+
+
+def parse_version(version: str):
+    try:
+        return tuple(int(part) for part in version.split("."))
+    except ValueError:
+        return None
+
+
+async def handshake_handler(request: web_request.Request):
+    error = check_client_key(request)
+    if error is not None:
+        return error
+
+    handshake = await request.json()
+
+    print(
+        f"Handshake from client {handshake.get('client_version')} "
+        f"({handshake.get('client_commit')})")
+    for template in handshake.get("templates", []):
+        print(
+            f"    Template {template.get('name') or 'default'}: "
+            f"Minecraft {template.get('minecraft_version')}, "
+            f"plugin {template.get('plugin_version')}")
+
+    client_version = parse_version(handshake.get("client_version", ""))
+    if client_version is None or client_version < minimum_client_version:
+        minimum = ".".join(str(part) for part in minimum_client_version)
+        return web.json_response({
+            "accepted": False,
+            "reason": f"SpicyGarden {minimum} or newer is required, please update",
+        })
+
+    client_formats = handshake.get("result_formats", [])
+
+    for result_format in accepted_result_formats:
+        if result_format in client_formats:
+            return web.json_response({
+                "accepted": True,
+                "result_format": result_format,
+                "accepts_previews": accept_previews,
+            })
+
+    return web.json_response({
+        "accepted": False,
+        "reason": "None of this client's result formats are accepted here",
+    })
+
+
+def sha256_file(path):
+    sha256 = hashlib.sha256()
+    with open(path, "rb") as file:
+        for chunk in iter(lambda: file.read(1024 * 1024), b""):
+            sha256.update(chunk)
+    return sha256.hexdigest()
+
+
+async def template_manifest_handler(request: web_request.Request):
+    error = check_client_key(request)
+    if error is not None:
+        return error
+
+    if not os.path.isdir(template_dir):
+        return web.Response(status=404, text="No template available")
+
+    files = []
+    for root, _, file_names in os.walk(template_dir):
+        for file_name in file_names:
+            path = os.path.join(root, file_name)
+            relative_path = os.path.relpath(path, template_dir).replace(os.sep, "/")
+            files.append({
+                "path": relative_path,
+                "sha256": sha256_file(path),
+                "url": f"{request.url.origin()}/template_files/{relative_path}",
+            })
+
+    return web.json_response({"files": files})
+
+
+async def client_update_handler(request: web_request.Request):
+    error = check_client_key(request)
+    if error is not None:
+        return error
+
+    if latest_client is None:
+        return web.Response(status=404, text="No updates available")
+
+    update = {
+        "version": latest_client["version"],
+        "notes": latest_client.get("notes"),
+    }
+
+    download = latest_client.get("downloads", {}).get(request.match_info["platform"])
+    if download is not None:
+        update.update(download)
+
+    return web.json_response(update)
+
+
+async def assign_handler(request: web_request.Request):
+    error = check_client_key(request)
+    if error is not None:
+        return error
+
     count = int(request.match_info.get("count", "1"))
 
-    seeds = "\n".join([seeds_to_search.pop() for _ in range(count)])
+    assignments = []
+    for _ in range(count):
+        seed, version = seeds_to_search.pop()
+        assignments.append(seed if version is None else f"{seed}\t{version}")
+
+    seeds = "\n".join(assignments)
 
     return web.Response(status=200, text=seeds)
 
 
 async def submit_handler(request: web_request.Request):
-    global valid_client_keys
+    error = check_client_key(request)
+    if error is not None:
+        return error
 
-    if "client_key" not in request.match_info:
-        return web.Response(status=401, text="Missing client key")
+    print(
+        f"Received submission for seed {request.headers['SpicyGarden-Seed']} "
+        f"(Minecraft {request.headers.get('SpicyGarden-Minecraft-Version', 'unknown')}, "
+        f"server jar {request.headers.get('SpicyGarden-Server-Jar-SHA256', 'unknown')[:12]}, "
+        f"plugin {request.headers.get('SpicyGarden-Plugin-SHA256', 'unknown')[:12]}, "
+        f"spawn {request.headers.get('SpicyGarden-Spawn', 'unknown')})")
+    result_format = request.headers.get("SpicyGarden-Result-Format", "raw")
+    if result_format == "raw":
+        print(await request.text())
+    else:
+        submission = await request.json()
+        summary = submission if result_format == "summary" else submission["summary"]
+        print(f"{result_format}: {summary}")
 
-    if request.match_info["client_key"] not in valid_client_keys:
-        return web.Response(status=401, text="Invalid client key")
+    client_key = get_client_key(request)
+    submission_counts[client_key] = submission_counts.get(client_key, 0) + 1
 
-    print(
-        f"Received submission for seed {request.headers['SpicyGarden-Seed']}")
-    print(await request.text())
+    return web.Response(status=204)
+
+
+async def submit_preview_handler(request: web_request.Request):
+    error = check_client_key(request)
+    if error is not None:
+        return error
+
+    if not accept_previews:
+        return web.Response(status=404, text="Previews aren't accepted here")
+
+    seed = request.headers["SpicyGarden-Seed"]
+    file_name = hashlib.sha256(seed.encode("utf-8")).hexdigest() + ".png"
+
+    os.makedirs(preview_dir, exist_ok=True)
+    with open(os.path.join(preview_dir, file_name), "wb") as file:
+        file.write(await request.read())
+
+    print(f"Received preview for seed {seed}, saved as {file_name}")
 
     return web.Response(status=204)
 
 
+async def my_stats_handler(request: web_request.Request):
+    error = check_client_key(request)
+    if error is not None:
+        return error
+
+    client_key = get_client_key(request)
+
+    return web.json_response({
+        "seeds_searched": submission_counts.get(client_key, 0),
+    })
+
 server = web.Application()
 
 server.add_routes([
+    web.get("/health", health_handler),
+    web.post("/handshake/{client_key}", handshake_handler),
+    web.get("/template_manifest/{client_key}", template_manifest_handler),
+    web.get("/client_update/{client_key}/{platform}", client_update_handler),
     web.get("/assign_seeds/{client_key}/{count}", assign_handler),
     web.post("/submit_result/{client_key}", submit_handler),
+    web.post("/submit_preview/{client_key}", submit_preview_handler),
+    web.get("/my_stats/{client_key}", my_stats_handler),
+    # Newer clients send their key in an Authorization header instead
+    web.post("/handshake", handshake_handler),
+    web.get("/template_manifest", template_manifest_handler),
+    web.get("/client_update/{platform}", client_update_handler),
+    web.get("/assign_seeds/{count}", assign_handler),
+    web.post("/submit_result", submit_handler),
+    web.post("/submit_preview", submit_preview_handler),
+    web.get("/my_stats", my_stats_handler),
 ])
 
+if os.path.isdir(template_dir):
+    server.router.add_static("/template_files/", template_dir)
+
 if __name__ == "__main__":
     web.run_app(server)
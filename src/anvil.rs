@@ -0,0 +1,323 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::Read;
+
+use flate2::read::{GzDecoder, ZlibDecoder};
+
+use crate::nbt::{self, Tag};
+
+const SECTOR_SIZE: usize = 4096;
+
+// Regions are 32x32 chunks, and chunks are 16x16 blocks
+const REGION_WIDTH: i32 = 32;
+const CHUNK_WIDTH: i32 = 16;
+
+// Heightmaps use 9 bits per column in every version that has them in this form (1.16 onwards)
+const HEIGHTMAP_BITS: usize = 9;
+
+// One region file (world/region/r.<x>.<z>.mca), read into memory
+struct RegionFile {
+    data: Vec<u8>,
+}
+
+impl RegionFile {
+    fn open(path: &str) -> Result<RegionFile, String> {
+        let data = fs::read(path).map_err(|e| format!("Unable to read {}: {}", path, e))?;
+
+        if data.len() < SECTOR_SIZE * 2 {
+            return Err(format!("{} is too short to be a region file", path));
+        }
+
+        Ok(RegionFile { data })
+    }
+
+    // Chunk coordinates are relative to the region, 0 to 31. Returns None for chunks
+    // that were never generated.
+    fn read_chunk(&self, local_x: i32, local_z: i32) -> Result<Option<Tag>, String> {
+        let header_index = 4 * (local_x + local_z * REGION_WIDTH) as usize;
+        let location = &self.data[header_index..header_index + 4];
+
+        let sector_offset = u32::from_be_bytes([0, location[0], location[1], location[2]]) as usize;
+        if sector_offset == 0 {
+            return Ok(None);
+        }
+
+        let start = sector_offset * SECTOR_SIZE;
+        if start + 5 > self.data.len() {
+            return Err("Chunk points past the end of its region file".to_string());
+        }
+
+        let length = u32::from_be_bytes([
+            self.data[start],
+            self.data[start + 1],
+            self.data[start + 2],
+            self.data[start + 3],
+        ]) as usize;
+        let compression = self.data[start + 4];
+
+        if length < 1 || start + 4 + length > self.data.len() {
+            return Err("Chunk runs past the end of its region file".to_string());
+        }
+
+        let payload = &self.data[start + 5..start + 4 + length];
+        let mut chunk_data = vec![];
+
+        let decompressed = match compression {
+            1 => GzDecoder::new(payload).read_to_end(&mut chunk_data),
+            2 => ZlibDecoder::new(payload).read_to_end(&mut chunk_data),
+            3 => {
+                chunk_data.extend_from_slice(payload);
+                Ok(payload.len())
+            }
+            // LZ4, and chunks too big to fit that live in their own .mcc file
+            compression => return Err(format!("Unsupported chunk compression {}", compression)),
+        };
+
+        decompressed.map_err(|e| format!("Unable to decompress chunk: {}", e))?;
+
+        nbt::read(&chunk_data).map(Some)
+    }
+}
+
+// Reads chunks out of a world's region files, keeping each file around once it's been opened
+pub struct RegionReader {
+    region_dir: String,
+    regions: HashMap<(i32, i32), Option<RegionFile>>,
+}
+
+impl RegionReader {
+    pub fn new(world_dir: &str) -> RegionReader {
+        RegionReader {
+            region_dir: format!("{}/region", world_dir),
+            regions: HashMap::new(),
+        }
+    }
+
+    // Returns None for chunks that don't exist, either because their region file
+    // doesn't or because they were never generated
+    pub fn read_chunk(&mut self, chunk_x: i32, chunk_z: i32) -> Result<Option<Chunk>, String> {
+        let region_key = (
+            chunk_x.div_euclid(REGION_WIDTH),
+            chunk_z.div_euclid(REGION_WIDTH),
+        );
+
+        let region_dir = &self.region_dir;
+        let region = self.regions.entry(region_key).or_insert_with(|| {
+            RegionFile::open(&format!(
+                "{}/r.{}.{}.mca",
+                region_dir, region_key.0, region_key.1
+            ))
+            .ok()
+        });
+
+        let region = match region {
+            Some(region) => region,
+            None => return Ok(None),
+        };
+
+        let chunk = region.read_chunk(
+            chunk_x.rem_euclid(REGION_WIDTH),
+            chunk_z.rem_euclid(REGION_WIDTH),
+        )?;
+
+        Ok(chunk.map(Chunk::new))
+    }
+}
+
+// Values packed into longs, as used by heightmaps and palettes. Since 1.16, values never
+// straddle two longs, so each long holds as many whole values as fit.
+fn unpack(longs: &[i64], bits: usize, index: usize) -> Option<usize> {
+    if bits == 0 {
+        return Some(0);
+    }
+
+    let values_per_long = 64 / bits;
+    let long = *longs.get(index / values_per_long)? as u64;
+    let shift = (index % values_per_long) * bits;
+
+    Some(((long >> shift) & ((1u64 << bits) - 1)) as usize)
+}
+
+// Bits needed to tell apart a palette's entries
+fn palette_bits(palette_length: usize) -> usize {
+    if palette_length <= 1 {
+        0
+    } else {
+        (usize::BITS - (palette_length - 1).leading_zeros()) as usize
+    }
+}
+
+// One generated chunk
+pub struct Chunk {
+    // Before 1.18, everything was wrapped in a "Level" compound
+    data: Tag,
+}
+
+impl Chunk {
+    fn new(root: Tag) -> Chunk {
+        let data = match root.get("Level") {
+            Some(level) => level.clone(),
+            None => root,
+        };
+
+        Chunk { data }
+    }
+
+    // Chunks around the edge of what's been generated may be half-done, with no terrain yet
+    pub fn is_fully_generated(&self) -> bool {
+        match self.data.get("Status").and_then(Tag::as_str) {
+            Some(status) => status == "full" || status == "minecraft:full",
+            None => false,
+        }
+    }
+
+    // The lowest block y in the chunk, which went below zero in 1.18
+    fn min_y(&self) -> i64 {
+        match self.data.get("yPos").and_then(Tag::as_i64) {
+            Some(lowest_section) => lowest_section * 16,
+            None => 0,
+        }
+    }
+
+    // The y of the highest non-air block in a column, from the WORLD_SURFACE heightmap.
+    // x and z are within the chunk, 0 to 15.
+    pub fn surface_height(&self, x: usize, z: usize) -> Option<i64> {
+        let heightmap = self
+            .data
+            .path(&["Heightmaps", "WORLD_SURFACE"])
+            .and_then(Tag::as_long_array)?;
+
+        // Heightmaps store the y just above the top block, counting from the bottom of the world
+        let stored = unpack(heightmap, HEIGHTMAP_BITS, z * 16 + x)? as i64;
+        Some(self.min_y() + stored - 1)
+    }
+
+    // The biome at a block, from 1.18's per-section biome palettes. Older versions
+    // store biomes as numeric ids, which would need a table per version to make sense of.
+    pub fn biome_at(&self, x: usize, y: i64, z: usize) -> Option<String> {
+        let section_y = y.div_euclid(16);
+
+        let section = self
+            .data
+            .get("sections")
+            .and_then(Tag::as_list)?
+            .iter()
+            .find(|section| section.get("Y").and_then(Tag::as_i64) == Some(section_y))?;
+
+        let biomes = section.get("biomes")?;
+        let palette = biomes.get("palette").and_then(Tag::as_list)?;
+
+        // Biomes are stored for each 4x4x4 cell
+        let cell_x = x / 4;
+        let cell_y = (y.rem_euclid(16) / 4) as usize;
+        let cell_z = z / 4;
+        let index = (cell_y * 4 + cell_z) * 4 + cell_x;
+
+        let palette_index = match biomes.get("data").and_then(Tag::as_long_array) {
+            Some(data) => unpack(data, palette_bits(palette.len()), index)?,
+            // A section with a single biome doesn't bother storing any data
+            None => 0,
+        };
+
+        palette
+            .get(palette_index)
+            .and_then(Tag::as_str)
+            .map(|biome| biome.to_string())
+    }
+}
+
+// Which chunk a block coordinate falls in
+pub fn chunk_of(block: i64) -> i32 {
+    block.div_euclid(CHUNK_WIDTH as i64) as i32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::Write;
+
+    use flate2::write::ZlibEncoder;
+    use flate2::Compression;
+
+    // An empty root compound holding Status: "minecraft:full"
+    fn chunk_nbt() -> Vec<u8> {
+        let mut data = vec![10, 0, 0, 8, 0, 6];
+        data.extend_from_slice(b"Status");
+        data.extend_from_slice(&14u16.to_be_bytes());
+        data.extend_from_slice(b"minecraft:full");
+        data.push(0);
+        data
+    }
+
+    // A region with just the chunk at 1,2, stored in the sector after the header.
+    // length is what the chunk's own header claims, which may be a lie.
+    fn region_with_chunk(sector_offset: u32, length: u32, compression: u8, payload: &[u8]) -> RegionFile {
+        let mut data = vec![0u8; SECTOR_SIZE * 2];
+
+        let header_index = 4 * (1 + 2 * REGION_WIDTH) as usize;
+        data[header_index..header_index + 3].copy_from_slice(&sector_offset.to_be_bytes()[1..]);
+        data[header_index + 3] = 1;
+
+        data.extend_from_slice(&length.to_be_bytes());
+        data.push(compression);
+        data.extend_from_slice(payload);
+        data.resize(SECTOR_SIZE * 3, 0);
+
+        RegionFile { data }
+    }
+
+    #[test]
+    fn chunks_are_read() {
+        let nbt = chunk_nbt();
+        let region = region_with_chunk(2, nbt.len() as u32 + 1, 3, &nbt);
+        let chunk = Chunk::new(region.read_chunk(1, 2).unwrap().unwrap());
+        assert!(chunk.is_fully_generated());
+
+        let mut encoder = ZlibEncoder::new(vec![], Compression::default());
+        encoder.write_all(&nbt).unwrap();
+        let compressed = encoder.finish().unwrap();
+        let region = region_with_chunk(2, compressed.len() as u32 + 1, 2, &compressed);
+        let chunk = Chunk::new(region.read_chunk(1, 2).unwrap().unwrap());
+        assert!(chunk.is_fully_generated());
+    }
+
+    #[test]
+    fn empty_chunk_slots_are_none() {
+        let nbt = chunk_nbt();
+        let region = region_with_chunk(2, nbt.len() as u32 + 1, 3, &nbt);
+        assert!(region.read_chunk(0, 0).unwrap().is_none());
+        assert!(region.read_chunk(31, 31).unwrap().is_none());
+    }
+
+    #[test]
+    fn unsupported_compression_is_an_error() {
+        let nbt = chunk_nbt();
+        // LZ4, then a chunk stored in its own .mcc file, then nonsense
+        for compression in &[4, 130, 99] {
+            let region = region_with_chunk(2, nbt.len() as u32 + 1, *compression, &nbt);
+            assert!(region.read_chunk(1, 2).is_err());
+        }
+    }
+
+    #[test]
+    fn chunks_past_the_end_of_the_file_are_an_error() {
+        let nbt = chunk_nbt();
+
+        // The chunk's sector starts past the end of the file
+        let region = region_with_chunk(3, nbt.len() as u32 + 1, 3, &nbt);
+        assert!(region.read_chunk(1, 2).is_err());
+        let region = region_with_chunk(0xFF_FFFF, nbt.len() as u32 + 1, 3, &nbt);
+        assert!(region.read_chunk(1, 2).is_err());
+
+        // The chunk claims to be longer than the file
+        let region = region_with_chunk(2, SECTOR_SIZE as u32, 3, &nbt);
+        assert!(region.read_chunk(1, 2).is_err());
+        let region = region_with_chunk(2, u32::MAX, 3, &nbt);
+        assert!(region.read_chunk(1, 2).is_err());
+
+        // And one that claims no length at all, not even for its compression type
+        let region = region_with_chunk(2, 0, 3, &nbt);
+        assert!(region.read_chunk(1, 2).is_err());
+    }
+}
@@ -0,0 +1,216 @@
+use std::time::Instant;
+
+use iced::canvas::{self, Canvas, Cursor, Frame, Geometry, Path, Stroke};
+use iced::{Color, Column, Length, Point, Rectangle, Row, Size};
+
+use crate::history::{self, SearchHistory};
+use crate::Message;
+
+const CHART_WIDTH: u16 = 380;
+const CHART_HEIGHT: u16 = 140;
+
+// Room left around the plot for the title and axis labels
+const TITLE_HEIGHT: f32 = 20.0;
+const LABEL_HEIGHT: f32 = 16.0;
+
+const THROUGHPUT_COLOR: Color = Color::from_rgb(0.2, 0.6, 0.3);
+const DURATION_COLOR: Color = Color::from_rgb(0.3, 0.4, 0.8);
+const FAILURE_COLOR: Color = Color::from_rgb(0.8, 0.3, 0.2);
+const TIMEOUT_COLOR: Color = Color::from_rgb(0.9, 0.6, 0.1);
+const BACKLOG_COLOR: Color = Color::from_rgb(0.5, 0.3, 0.7);
+const AXIS_COLOR: Color = Color::from_rgb(0.6, 0.6, 0.6);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ChartStyle {
+    Bars,
+    Lines,
+}
+
+// A small self-scaling chart of one or more series sharing the same x axis
+struct Chart {
+    title: String,
+    style: ChartStyle,
+    series: Vec<(Color, Vec<f32>)>,
+    // Fixes the top of the y axis instead of scaling to the largest value
+    y_max: Option<f32>,
+    x_start_label: String,
+    x_end_label: String,
+}
+
+impl Chart {
+    fn y_max(&self) -> f32 {
+        let largest = self
+            .series
+            .iter()
+            .flat_map(|(_, values)| values.iter().cloned())
+            .fold(0.0, f32::max);
+
+        self.y_max.unwrap_or(largest).max(1.0)
+    }
+}
+
+impl canvas::Program<Message> for Chart {
+    fn draw(&self, bounds: Rectangle, _cursor: Cursor) -> Vec<Geometry> {
+        let mut frame = Frame::new(bounds.size());
+
+        let y_max = self.y_max();
+
+        frame.fill_text(canvas::Text {
+            content: format!("{} (max {:.1})", self.title, y_max),
+            position: Point::new(0.0, 0.0),
+            color: Color::BLACK,
+            size: 16.0,
+            ..canvas::Text::default()
+        });
+
+        let plot_top = TITLE_HEIGHT;
+        let plot_bottom = frame.height() - LABEL_HEIGHT;
+        let plot_height = plot_bottom - plot_top;
+        let plot_width = frame.width();
+
+        frame.stroke(
+            &Path::line(Point::new(0.0, plot_bottom), Point::new(plot_width, plot_bottom)),
+            Stroke {
+                color: AXIS_COLOR,
+                width: 1.0,
+                ..Stroke::default()
+            },
+        );
+
+        let to_y = |value: f32| plot_bottom - (value / y_max).min(1.0) * plot_height;
+
+        for (series_index, (color, values)) in self.series.iter().enumerate() {
+            if values.is_empty() {
+                continue;
+            }
+
+            let slot_width = plot_width / values.len() as f32;
+
+            match self.style {
+                ChartStyle::Bars => {
+                    // Series share each slot side by side
+                    let bar_width = (slot_width / self.series.len() as f32 - 1.0).max(1.0);
+
+                    for (index, value) in values.iter().enumerate() {
+                        if *value <= 0.0 {
+                            continue;
+                        }
+
+                        let x = index as f32 * slot_width + series_index as f32 * (bar_width + 1.0);
+                        let top = to_y(*value);
+                        frame.fill_rectangle(
+                            Point::new(x, top),
+                            Size::new(bar_width, plot_bottom - top),
+                            *color,
+                        );
+                    }
+                }
+                ChartStyle::Lines => {
+                    let line = Path::new(|builder| {
+                        for (index, value) in values.iter().enumerate() {
+                            let point = Point::new((index as f32 + 0.5) * slot_width, to_y(*value));
+                            if index == 0 {
+                                builder.move_to(point);
+                            } else {
+                                builder.line_to(point);
+                            }
+                        }
+                    });
+
+                    frame.stroke(
+                        &line,
+                        Stroke {
+                            color: *color,
+                            width: 2.0,
+                            ..Stroke::default()
+                        },
+                    );
+                }
+            };
+        }
+
+        frame.fill_text(canvas::Text {
+            content: self.x_start_label.clone(),
+            position: Point::new(0.0, plot_bottom + 2.0),
+            color: AXIS_COLOR,
+            size: 12.0,
+            ..canvas::Text::default()
+        });
+
+        frame.fill_text(canvas::Text {
+            content: self.x_end_label.clone(),
+            position: Point::new(plot_width, plot_bottom + 2.0),
+            color: AXIS_COLOR,
+            size: 12.0,
+            horizontal_alignment: iced::alignment::Horizontal::Right,
+            ..canvas::Text::default()
+        });
+
+        vec![frame.into_geometry()]
+    }
+}
+
+fn chart_canvas(chart: Chart) -> Canvas<Message, Chart> {
+    Canvas::new(chart)
+        .width(Length::Units(CHART_WIDTH))
+        .height(Length::Units(CHART_HEIGHT))
+}
+
+pub fn view_charts<'a>(history: &SearchHistory) -> Column<'a, Message> {
+    let now = Instant::now();
+
+    let throughput = Chart {
+        title: "Seeds per minute".to_string(),
+        style: ChartStyle::Bars,
+        series: vec![(THROUGHPUT_COLOR, history.throughput_per_minute(now))],
+        y_max: None,
+        x_start_label: "60 min ago".to_string(),
+        x_end_label: "now".to_string(),
+    };
+
+    let durations = Chart {
+        title: "Seed durations".to_string(),
+        style: ChartStyle::Bars,
+        series: vec![(DURATION_COLOR, history.duration_histogram())],
+        y_max: None,
+        x_start_label: "0s".to_string(),
+        x_end_label: format!(
+            "{}s+",
+            history::DURATION_BIN_SECONDS * (history::DURATION_BIN_COUNT as u64 - 1)
+        ),
+    };
+
+    let (failure_rates, timeout_rates) = history.outcome_rates(now);
+    let outcomes = Chart {
+        title: "Failure / timeout %".to_string(),
+        style: ChartStyle::Bars,
+        series: vec![(FAILURE_COLOR, failure_rates), (TIMEOUT_COLOR, timeout_rates)],
+        y_max: Some(100.0),
+        x_start_label: "60 min ago".to_string(),
+        x_end_label: "now".to_string(),
+    };
+
+    let backlog = Chart {
+        title: format!("Pending submissions ({} now)", history.pending_submissions()),
+        style: ChartStyle::Lines,
+        series: vec![(BACKLOG_COLOR, history.backlog_per_minute(now))],
+        y_max: None,
+        x_start_label: "60 min ago".to_string(),
+        x_end_label: "now".to_string(),
+    };
+
+    Column::new()
+        .spacing(8)
+        .push(
+            Row::new()
+                .spacing(8)
+                .push(chart_canvas(throughput))
+                .push(chart_canvas(durations)),
+        )
+        .push(
+            Row::new()
+                .spacing(8)
+                .push(chart_canvas(outcomes))
+                .push(chart_canvas(backlog)),
+        )
+}
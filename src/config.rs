@@ -0,0 +1,472 @@
+use std::convert::TryFrom;
+use std::env;
+use std::fmt;
+use std::fs;
+
+use crate::credentials::{self, KeyStorage};
+use crate::rules::Rule;
+use crate::runner;
+
+pub const DEFAULT_CONFIG_PATH: &str = "config.toml";
+
+// Bump this and add a migration below whenever the meaning of an existing key changes.
+// Files without a config_version are from before versioning, and count as version 1.
+pub const CONFIG_VERSION: i64 = 2;
+
+#[derive(Clone)]
+pub struct Config {
+    pub server_address: String,
+    pub client_key: String,
+    pub client_key_storage: KeyStorage,
+    pub runner_count: u32,
+    // Whether to keep completed results in a local database as well as sending them off
+    pub store_results: bool,
+    // [[rules]] tables, for picking out interesting seeds as they're searched
+    pub rules: Vec<Rule>,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            server_address: "".to_string(),
+            client_key: "".to_string(),
+            // load_config swaps this for KeyStorage::preferred() when the file doesn't say
+            client_key_storage: KeyStorage::File,
+            runner_count: 1,
+            store_results: false,
+            rules: vec![],
+        }
+    }
+}
+
+// Something wrong with the config, pointing at the offending key and line where we know them
+#[derive(Debug, Clone)]
+pub struct ConfigError {
+    pub key: Option<String>,
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (&self.key, self.line) {
+            (Some(key), Some(line)) => write!(f, "line {}, `{}`: {}", line, key, self.message),
+            (Some(key), None) => write!(f, "`{}`: {}", key, self.message),
+            (None, Some(line)) => write!(f, "line {}: {}", line, self.message),
+            (None, None) => write!(f, "{}", self.message),
+        }
+    }
+}
+
+impl ConfigError {
+    fn for_key(key: &str, message: String) -> ConfigError {
+        ConfigError {
+            key: Some(key.to_string()),
+            line: None,
+            message,
+        }
+    }
+}
+
+impl Config {
+    // Checks values that parse fine but still can't work
+    pub fn validate(&self) -> Vec<ConfigError> {
+        let mut errors = vec![];
+
+        if !self.server_address.is_empty()
+            && !self.server_address.starts_with("http://")
+            && !self.server_address.starts_with("https://")
+        {
+            errors.push(ConfigError::for_key(
+                "server_address",
+                "must start with http:// or https://".to_string(),
+            ));
+        }
+
+        if self.client_key.contains('/') {
+            errors.push(ConfigError::for_key(
+                "client_key",
+                "must not contain '/'".to_string(),
+            ));
+        }
+
+        if self.runner_count < 1 {
+            errors.push(ConfigError::for_key(
+                "runner_count",
+                "must be at least 1".to_string(),
+            ));
+        }
+
+        if self.runner_count > runner::MAX_RUNNER_COUNT {
+            errors.push(ConfigError::for_key(
+                "runner_count",
+                format!("must be at most {}", runner::MAX_RUNNER_COUNT),
+            ));
+        }
+
+        errors
+    }
+}
+
+// Values given on the command line or in the environment, which win over the config file
+#[derive(Default, Clone)]
+pub struct ConfigOverrides {
+    pub server_address: Option<String>,
+    pub client_key: Option<String>,
+    pub runner_count: Option<String>,
+}
+
+impl ConfigOverrides {
+    // Reads SPICYGARDEN_* environment variables, then takes any matching
+    // command line options out of `args`. Command line options win.
+    pub fn from_env_and_args(args: &mut Vec<String>) -> Result<ConfigOverrides, String> {
+        let mut overrides = ConfigOverrides {
+            server_address: env::var("SPICYGARDEN_SERVER_ADDRESS").ok(),
+            client_key: env::var("SPICYGARDEN_CLIENT_KEY").ok(),
+            runner_count: env::var("SPICYGARDEN_RUNNER_COUNT").ok(),
+        };
+
+        if let Some(value) = take_option(args, "--server-address")? {
+            overrides.server_address = Some(value);
+        }
+        if let Some(value) = take_option(args, "--client-key")? {
+            overrides.client_key = Some(value);
+        }
+        if let Some(value) = take_option(args, "--runner-count")? {
+            overrides.runner_count = Some(value);
+        }
+
+        Ok(overrides)
+    }
+
+    // Puts the file's value back wherever a setting is still just what it was overridden
+    // with, so that saving only writes what the user actually changed
+    pub fn keep_out_of(&self, mut settings: Config, file_config: &Config) -> Config {
+        if self.server_address.as_ref() == Some(&settings.server_address) {
+            settings.server_address = file_config.server_address.clone();
+        }
+        if self.client_key.as_ref() == Some(&settings.client_key) {
+            settings.client_key = file_config.client_key.clone();
+        }
+        let runner_count_override = self
+            .runner_count
+            .as_ref()
+            .and_then(|value| value.parse::<u32>().ok());
+        if runner_count_override == Some(settings.runner_count) {
+            settings.runner_count = file_config.runner_count;
+        }
+
+        settings
+    }
+
+    fn apply(&self, config: &mut Config, errors: &mut Vec<ConfigError>) {
+        if let Some(value) = &self.server_address {
+            config.server_address = value.clone();
+        }
+        if let Some(value) = &self.client_key {
+            config.client_key = value.clone();
+        }
+        if let Some(value) = &self.runner_count {
+            match value.parse::<u32>() {
+                Ok(runner_count) => config.runner_count = runner_count,
+                Err(_) => errors.push(ConfigError::for_key(
+                    "runner_count",
+                    format!("override {:?} isn't a whole number", value),
+                )),
+            }
+        }
+    }
+}
+
+// Removes `--name value` from args, returning the value if it was there
+pub fn take_option(args: &mut Vec<String>, name: &str) -> Result<Option<String>, String> {
+    let option_index = match args.iter().position(|arg| arg == name) {
+        Some(option_index) => option_index,
+        None => return Ok(None),
+    };
+
+    if option_index + 1 >= args.len() {
+        return Err(format!("{} needs a value", name));
+    }
+
+    let value = args.remove(option_index + 1);
+    args.remove(option_index);
+    Ok(Some(value))
+}
+
+// Which line of the file a top-level key is set on, counting from 1
+fn find_key_line(text: &str, key: &str) -> Option<usize> {
+    text.lines()
+        .position(|line| match line.trim_start().strip_prefix(key) {
+            Some(rest) => rest.trim_start().starts_with('='),
+            None => false,
+        })
+        .map(|index| index + 1)
+}
+
+// Brings a table from an older config version up to CONFIG_VERSION, one version at a time
+fn migrate(table: &mut toml::value::Table, text: &str, errors: &mut Vec<ConfigError>) {
+    let mut version = match table.get("config_version") {
+        None => 1,
+        Some(toml::Value::Integer(version)) => *version,
+        Some(_) => {
+            errors.push(ConfigError {
+                key: Some("config_version".to_string()),
+                line: find_key_line(text, "config_version"),
+                message: "must be a whole number".to_string(),
+            });
+            return;
+        }
+    };
+
+    if version > CONFIG_VERSION {
+        errors.push(ConfigError {
+            key: Some("config_version".to_string()),
+            line: find_key_line(text, "config_version"),
+            message: format!(
+                "version {} is newer than this build of SpicyGarden understands ({})",
+                version, CONFIG_VERSION
+            ),
+        });
+        return;
+    }
+
+    // Each migration brings the table up to the next version, oldest first.
+    // Version 2 only started recording the version, so version 1 files need no changes.
+    if version < 2 {
+        version = 2;
+    }
+
+    table.insert("config_version".to_string(), toml::Value::Integer(version));
+}
+
+fn read_string(
+    table: &toml::value::Table,
+    text: &str,
+    key: &str,
+    errors: &mut Vec<ConfigError>,
+) -> Option<String> {
+    match table.get(key)? {
+        toml::Value::String(value) => Some(value.clone()),
+        other => {
+            errors.push(ConfigError {
+                key: Some(key.to_string()),
+                line: find_key_line(text, key),
+                message: format!("expected a string, found {}", other.type_str()),
+            });
+            None
+        }
+    }
+}
+
+fn read_u32(
+    table: &toml::value::Table,
+    text: &str,
+    key: &str,
+    errors: &mut Vec<ConfigError>,
+) -> Option<u32> {
+    let message = match table.get(key)? {
+        toml::Value::Integer(value) => match u32::try_from(*value) {
+            Ok(value) => return Some(value),
+            Err(_) => format!("{} is out of range", value),
+        },
+        other => format!("expected a whole number, found {}", other.type_str()),
+    };
+
+    errors.push(ConfigError {
+        key: Some(key.to_string()),
+        line: find_key_line(text, key),
+        message,
+    });
+    None
+}
+
+fn read_bool(
+    table: &toml::value::Table,
+    text: &str,
+    key: &str,
+    errors: &mut Vec<ConfigError>,
+) -> Option<bool> {
+    match table.get(key)? {
+        toml::Value::Boolean(value) => Some(*value),
+        other => {
+            errors.push(ConfigError {
+                key: Some(key.to_string()),
+                line: find_key_line(text, key),
+                message: format!("expected true or false, found {}", other.type_str()),
+            });
+            None
+        }
+    }
+}
+
+// Which line the nth `[[name]]` table starts on, counting from 1
+fn find_table_array_line(text: &str, name: &str, index: usize) -> Option<usize> {
+    let header = format!("[[{}]]", name);
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| line.trim() == header)
+        .nth(index)
+        .map(|(line_index, _)| line_index + 1)
+}
+
+// Each rule that can't be used is left out, rather than throwing away all of them
+fn read_rules(table: &toml::value::Table, text: &str, errors: &mut Vec<ConfigError>) -> Vec<Rule> {
+    let rule_values = match table.get("rules") {
+        None => return vec![],
+        Some(toml::Value::Array(rule_values)) => rule_values,
+        Some(other) => {
+            errors.push(ConfigError {
+                key: Some("rules".to_string()),
+                line: find_key_line(text, "rules"),
+                message: format!("expected [[rules]] tables, found {}", other.type_str()),
+            });
+            return vec![];
+        }
+    };
+
+    let mut rules = vec![];
+
+    for (index, rule_value) in rule_values.iter().enumerate() {
+        let rule = rule_value
+            .clone()
+            .try_into::<Rule>()
+            .map_err(|e| e.to_string())
+            .and_then(|rule| rule.validate().map(|_| rule));
+
+        match rule {
+            Ok(rule) => rules.push(rule),
+            Err(message) => errors.push(ConfigError {
+                key: Some("rules".to_string()),
+                line: find_table_array_line(text, "rules", index),
+                message,
+            }),
+        };
+    }
+
+    rules
+}
+
+// Loads the config file and applies overrides on top of it. Problems don't stop
+// anything from loading: each key that can't be used keeps its default value,
+// and the problems are returned so they can be shown to the user.
+//
+// Returns the config to run with, then the config as the file alone has it,
+// which is what saving should start from so that overrides stay out of the file.
+pub fn load_config(
+    config_path: &str,
+    overrides: &ConfigOverrides,
+) -> (Config, Config, Vec<ConfigError>) {
+    let mut config = Config::default();
+    let mut errors = vec![];
+
+    let text = match fs::read_to_string(config_path) {
+        Ok(text) => text,
+        Err(_) => {
+            println!("Couldn't read {}, using default values.", config_path);
+            "".to_string()
+        }
+    };
+
+    let mut table = match toml::from_str::<toml::value::Table>(&text) {
+        Ok(table) => table,
+        Err(e) => {
+            errors.push(ConfigError {
+                key: None,
+                line: e.line_col().map(|(line, _column)| line + 1),
+                message: format!("not valid TOML: {}", e),
+            });
+            toml::value::Table::new()
+        }
+    };
+
+    migrate(&mut table, &text, &mut errors);
+
+    if let Some(value) = read_string(&table, &text, "server_address", &mut errors) {
+        config.server_address = value;
+    }
+    if let Some(value) = read_string(&table, &text, "client_key", &mut errors) {
+        config.client_key = value;
+    }
+    if let Some(value) = read_u32(&table, &text, "runner_count", &mut errors) {
+        config.runner_count = value;
+    }
+    if let Some(value) = read_bool(&table, &text, "store_results", &mut errors) {
+        config.store_results = value;
+    }
+    config.rules = read_rules(&table, &text, &mut errors);
+    if let Some(value) = read_string(&table, &text, "client_key_storage", &mut errors) {
+        match KeyStorage::from_config_value(&value) {
+            Some(storage) => config.client_key_storage = storage,
+            None => errors.push(ConfigError {
+                key: Some("client_key_storage".to_string()),
+                line: find_key_line(&text, "client_key_storage"),
+                message: format!(
+                    "{:?} isn't one of \"config\", \"file\" or \"secret-service\"",
+                    value
+                ),
+            }),
+        }
+    } else if !table.contains_key("client_key_storage") {
+        config.client_key_storage = KeyStorage::preferred();
+    }
+
+    // A key still sitting in config.toml is used until the next save moves it into storage
+    match credentials::load_client_key(config.client_key_storage) {
+        Ok(Some(client_key)) => config.client_key = client_key,
+        Ok(None) => (),
+        Err(e) => errors.push(ConfigError {
+            key: Some("client_key_storage".to_string()),
+            line: find_key_line(&text, "client_key_storage"),
+            message: e,
+        }),
+    };
+
+    let file_config = config.clone();
+    overrides.apply(&mut config, &mut errors);
+
+    for mut error in config.validate() {
+        if let Some(key) = &error.key {
+            error.line = find_key_line(&text, key);
+        }
+        errors.push(error);
+    }
+
+    for error in &errors {
+        println!("ERROR: {} {}", config_path, error);
+    }
+
+    (config, file_config, errors)
+}
+
+// Writes the config back out. Rather than serializing from scratch, this edits
+// whatever is already in the file, so that comments and keys we don't know
+// about survive the trip.
+pub fn save_config(config_path: &str, config: &Config) -> Result<(), String> {
+    let existing_text = fs::read_to_string(config_path).unwrap_or_default();
+
+    let mut document = match existing_text.parse::<toml_edit::Document>() {
+        Ok(document) => document,
+        Err(e) => {
+            return Err(format!(
+                "Not saving, since {} isn't valid TOML and would be overwritten: {}",
+                config_path, e
+            ));
+        }
+    };
+
+    document["config_version"] = toml_edit::value(CONFIG_VERSION);
+    document["server_address"] = toml_edit::value(config.server_address.clone());
+    document["client_key_storage"] = toml_edit::value(config.client_key_storage.config_value());
+    document["runner_count"] = toml_edit::value(config.runner_count as i64);
+
+    if config.client_key_storage == KeyStorage::Config {
+        document["client_key"] = toml_edit::value(config.client_key.clone());
+    } else {
+        credentials::store_client_key(config.client_key_storage, &config.client_key)?;
+        document.as_table_mut().remove("client_key");
+    }
+
+    fs::write(config_path, document.to_string())
+        .map_err(|e| format!("Unable to write {}: {}", config_path, e))
+}
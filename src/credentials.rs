@@ -0,0 +1,178 @@
+use std::fmt;
+use std::fs;
+use std::io::Write;
+
+use serde::{Deserialize, Serialize};
+
+pub const CREDENTIALS_FILE_PATH: &str = "credentials.toml";
+
+#[cfg(feature = "secret-service")]
+const SECRET_SERVICE_NAME: &str = "SpicyGarden";
+#[cfg(feature = "secret-service")]
+const SECRET_SERVICE_USER: &str = "client_key";
+
+// Where the client key lives when it isn't in config.toml
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyStorage {
+    // Plaintext in config.toml, like it always has been
+    Config,
+    // In credentials.toml, readable only by the current user
+    File,
+    // In the desktop's keyring, via the freedesktop Secret Service
+    SecretService,
+}
+
+impl KeyStorage {
+    // Every choice this build can actually use, for the GUI to pick from
+    #[cfg(feature = "secret-service")]
+    pub const AVAILABLE: &'static [KeyStorage] =
+        &[KeyStorage::SecretService, KeyStorage::File, KeyStorage::Config];
+    #[cfg(not(feature = "secret-service"))]
+    pub const AVAILABLE: &'static [KeyStorage] = &[KeyStorage::File, KeyStorage::Config];
+
+    // Used when config.toml doesn't say. The keyring is best, but only if there's one to talk to.
+    pub fn preferred() -> KeyStorage {
+        if secret_service_available() {
+            KeyStorage::SecretService
+        } else {
+            KeyStorage::File
+        }
+    }
+
+    pub fn from_config_value(value: &str) -> Option<KeyStorage> {
+        match value {
+            "config" => Some(KeyStorage::Config),
+            "file" => Some(KeyStorage::File),
+            "secret-service" => Some(KeyStorage::SecretService),
+            _ => None,
+        }
+    }
+
+    pub fn config_value(&self) -> &'static str {
+        match self {
+            KeyStorage::Config => "config",
+            KeyStorage::File => "file",
+            KeyStorage::SecretService => "secret-service",
+        }
+    }
+}
+
+impl fmt::Display for KeyStorage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let description = match self {
+            KeyStorage::Config => "In config.toml (plaintext)",
+            KeyStorage::File => "In credentials.toml",
+            KeyStorage::SecretService => "In the desktop keyring",
+        };
+        write!(f, "{}", description)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct CredentialsFile {
+    client_key: String,
+}
+
+fn load_from_file() -> Result<Option<String>, String> {
+    let toml_slice = match fs::read(CREDENTIALS_FILE_PATH) {
+        Ok(toml_slice) => toml_slice,
+        Err(_) => return Ok(None),
+    };
+
+    toml::from_slice::<CredentialsFile>(&toml_slice)
+        .map(|credentials| Some(credentials.client_key))
+        .map_err(|e| format!("Failed to parse {}: {}", CREDENTIALS_FILE_PATH, e))
+}
+
+fn store_in_file(client_key: &str) -> Result<(), String> {
+    let toml_text = toml::to_string(&CredentialsFile {
+        client_key: client_key.to_string(),
+    })
+    .map_err(|e| format!("Failed to serialize credentials: {}", e))?;
+
+    let mut open_options = fs::OpenOptions::new();
+    open_options.write(true).create(true).truncate(true);
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+
+        open_options.mode(0o600);
+
+        // The mode above only applies to new files, so tighten up an existing one too
+        if let Ok(metadata) = fs::metadata(CREDENTIALS_FILE_PATH) {
+            let mut permissions = metadata.permissions();
+            permissions.set_mode(0o600);
+            fs::set_permissions(CREDENTIALS_FILE_PATH, permissions)
+                .map_err(|e| format!("Unable to restrict {}: {}", CREDENTIALS_FILE_PATH, e))?;
+        };
+    }
+
+    let mut credentials_file = open_options
+        .open(CREDENTIALS_FILE_PATH)
+        .map_err(|e| format!("Unable to open {}: {}", CREDENTIALS_FILE_PATH, e))?;
+
+    credentials_file
+        .write_all(toml_text.as_bytes())
+        .map_err(|e| format!("Unable to write {}: {}", CREDENTIALS_FILE_PATH, e))
+}
+
+#[cfg(feature = "secret-service")]
+fn load_from_secret_service() -> Result<Option<String>, String> {
+    let entry = keyring::Entry::new(SECRET_SERVICE_NAME, SECRET_SERVICE_USER);
+
+    match entry.get_password() {
+        Ok(client_key) => Ok(Some(client_key)),
+        Err(keyring::Error::NoEntry) => Ok(None),
+        Err(e) => Err(format!("Unable to read client key from Secret Service: {}", e)),
+    }
+}
+
+// Whether the keyring answers at all. Not having our entry yet still counts.
+#[cfg(feature = "secret-service")]
+fn secret_service_available() -> bool {
+    matches!(
+        keyring::Entry::new(SECRET_SERVICE_NAME, SECRET_SERVICE_USER).get_password(),
+        Ok(_) | Err(keyring::Error::NoEntry)
+    )
+}
+
+#[cfg(feature = "secret-service")]
+fn store_in_secret_service(client_key: &str) -> Result<(), String> {
+    keyring::Entry::new(SECRET_SERVICE_NAME, SECRET_SERVICE_USER)
+        .set_password(client_key)
+        .map_err(|e| format!("Unable to store client key in Secret Service: {}", e))
+}
+
+#[cfg(not(feature = "secret-service"))]
+fn load_from_secret_service() -> Result<Option<String>, String> {
+    Err("This build of SpicyGarden doesn't include Secret Service support".to_string())
+}
+
+#[cfg(not(feature = "secret-service"))]
+fn secret_service_available() -> bool {
+    false
+}
+
+#[cfg(not(feature = "secret-service"))]
+fn store_in_secret_service(_client_key: &str) -> Result<(), String> {
+    Err("This build of SpicyGarden doesn't include Secret Service support".to_string())
+}
+
+// Looks the client key up wherever it's stored. Keys kept in config.toml are
+// read along with the rest of the config, so there's nothing to do for those.
+pub fn load_client_key(storage: KeyStorage) -> Result<Option<String>, String> {
+    match storage {
+        KeyStorage::Config => Ok(None),
+        KeyStorage::File => load_from_file(),
+        KeyStorage::SecretService => load_from_secret_service(),
+    }
+}
+
+pub fn store_client_key(storage: KeyStorage, client_key: &str) -> Result<(), String> {
+    match storage {
+        KeyStorage::Config => Ok(()),
+        KeyStorage::File => store_in_file(client_key),
+        KeyStorage::SecretService => store_in_secret_service(client_key),
+    }
+}
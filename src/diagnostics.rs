@@ -0,0 +1,257 @@
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+use iced::{Color, Column, Length, Row, Text};
+
+use crate::servertemplate::{self, ServerTemplate, SERVER_TEMPLATES_DIR, SERVER_TEMPLATE_DIR};
+use crate::Message;
+
+const RUNNERS_DIR: &str = "runners";
+
+// Newer Minecraft releases won't start on anything older
+const RECOMMENDED_JAVA_VERSION: u32 = 17;
+
+// Roughly how much a generated world takes up on top of the template, with our small view distance
+const WORLD_SIZE_ESTIMATE: u64 = 64 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CheckStatus {
+    Passed,
+    Warning,
+    Failed,
+}
+
+#[derive(Debug, Clone)]
+pub struct DiagnosticCheck {
+    pub name: String,
+    pub status: CheckStatus,
+    pub detail: String,
+}
+
+impl DiagnosticCheck {
+    fn new(name: &str, status: CheckStatus, detail: String) -> DiagnosticCheck {
+        DiagnosticCheck {
+            name: name.to_string(),
+            status,
+            detail,
+        }
+    }
+}
+
+pub fn any_failed(checks: &[DiagnosticCheck]) -> bool {
+    checks.iter().any(|check| check.status == CheckStatus::Failed)
+}
+
+fn check_file(name: &str, path: &str) -> DiagnosticCheck {
+    if Path::new(path).is_file() {
+        DiagnosticCheck::new(name, CheckStatus::Passed, format!("Found {}", path))
+    } else {
+        DiagnosticCheck::new(name, CheckStatus::Failed, format!("{} is missing", path))
+    }
+}
+
+fn check_eula(template: &ServerTemplate) -> DiagnosticCheck {
+    let name = format!("EULA ({})", template.display_name());
+    let eula_path = format!("{}/eula.txt", template.dir);
+
+    match fs::read_to_string(&eula_path) {
+        Ok(eula) => {
+            if eula.lines().any(|line| line.trim() == "eula=true") {
+                DiagnosticCheck::new(&name, CheckStatus::Passed, "Accepted".to_string())
+            } else {
+                DiagnosticCheck::new(
+                    &name,
+                    CheckStatus::Failed,
+                    format!("{} doesn't contain eula=true", eula_path),
+                )
+            }
+        }
+        Err(_) => DiagnosticCheck::new(&name, CheckStatus::Failed, format!("{} is missing", eula_path)),
+    }
+}
+
+// Pulls the major version out of `java -version` output, which looks like
+// `openjdk version "17.0.4" 2022-07-19` or, for older releases, `java version "1.8.0_341"`
+fn parse_java_major_version(output: &str) -> Option<u32> {
+    let version = output.split('"').nth(1)?;
+    let mut components = version.split(['.', '_', '-']);
+
+    match components.next()?.parse::<u32>().ok()? {
+        1 => components.next()?.parse::<u32>().ok(),
+        major => Some(major),
+    }
+}
+
+fn check_java(java: &str) -> DiagnosticCheck {
+    let name = if java == "java" {
+        "Java".to_string()
+    } else {
+        format!("Java ({})", java)
+    };
+
+    let output = match Command::new(java).arg("-version").output() {
+        Ok(output) => output,
+        Err(e) => {
+            return DiagnosticCheck::new(
+                &name,
+                CheckStatus::Failed,
+                format!("Unable to run {}: {}", java, e),
+            );
+        }
+    };
+
+    // java -version prints to stderr
+    let version_text = String::from_utf8_lossy(&output.stderr);
+
+    match parse_java_major_version(&version_text) {
+        Some(major) if major >= RECOMMENDED_JAVA_VERSION => {
+            DiagnosticCheck::new(&name, CheckStatus::Passed, format!("Java {}", major))
+        }
+        Some(major) => DiagnosticCheck::new(
+            &name,
+            CheckStatus::Warning,
+            format!(
+                "Java {} is older than {}, which recent Minecraft versions need",
+                major, RECOMMENDED_JAVA_VERSION
+            ),
+        ),
+        None => DiagnosticCheck::new(
+            &name,
+            CheckStatus::Warning,
+            "Found java, but couldn't tell which version it is".to_string(),
+        ),
+    }
+}
+
+fn check_runners_writable() -> DiagnosticCheck {
+    let probe_path = format!("{}/.spicygarden_write_test", RUNNERS_DIR);
+
+    let result = fs::create_dir_all(RUNNERS_DIR)
+        .and_then(|_| fs::write(&probe_path, b"test"))
+        .and_then(|_| fs::remove_file(&probe_path));
+
+    match result {
+        Ok(()) => DiagnosticCheck::new(
+            "Runner directory",
+            CheckStatus::Passed,
+            format!("{}/ is writable", RUNNERS_DIR),
+        ),
+        Err(e) => DiagnosticCheck::new(
+            "Runner directory",
+            CheckStatus::Failed,
+            format!("Unable to write to {}/: {}", RUNNERS_DIR, e),
+        ),
+    }
+}
+
+fn check_disk_space(templates: &[ServerTemplate], runner_count: u32) -> DiagnosticCheck {
+    // Any runner might end up with the biggest template
+    let template_size = templates
+        .iter()
+        .map(|template| fs_extra::dir::get_size(&template.dir).unwrap_or(0))
+        .max()
+        .unwrap_or(0);
+    let needed = (template_size + WORLD_SIZE_ESTIMATE) * runner_count as u64;
+
+    let available = match fs2::available_space(".") {
+        Ok(available) => available,
+        Err(e) => {
+            return DiagnosticCheck::new(
+                "Disk space",
+                CheckStatus::Warning,
+                format!("Unable to check free disk space: {}", e),
+            );
+        }
+    };
+
+    let megabytes = |bytes: u64| bytes / (1024 * 1024);
+
+    let status = if available >= needed {
+        CheckStatus::Passed
+    } else {
+        CheckStatus::Failed
+    };
+
+    DiagnosticCheck::new(
+        "Disk space",
+        status,
+        format!(
+            "{} MB free, about {} MB needed for {} runners",
+            megabytes(available),
+            megabytes(needed),
+            runner_count
+        ),
+    )
+}
+
+// Checks everything a runner needs before it's asked to run, so problems show up
+// here instead of as a failure on every single seed
+pub fn run_diagnostics(runner_count: u32) -> Vec<DiagnosticCheck> {
+    let mut checks = vec![];
+
+    let templates = servertemplate::find_templates();
+
+    if templates.is_empty() {
+        checks.push(DiagnosticCheck::new(
+            "Server template",
+            CheckStatus::Failed,
+            format!(
+                "Neither {}/ nor {}/<version>/ exists",
+                SERVER_TEMPLATE_DIR, SERVER_TEMPLATES_DIR
+            ),
+        ));
+    }
+
+    for template in &templates {
+        checks.push(check_file(
+            &format!("Server jar ({})", template.display_name()),
+            &template.jar_path(),
+        ));
+        checks.push(check_file(
+            &format!("SpicyGarden plugin ({})", template.display_name()),
+            &template.plugin_path(),
+        ));
+        checks.push(check_eula(template));
+    }
+
+    // Templates can each ask for their own java, but there's no need to check one twice
+    let mut javas: Vec<&str> = templates.iter().map(|template| template.java.as_str()).collect();
+    javas.sort_unstable();
+    javas.dedup();
+    if javas.is_empty() {
+        javas.push("java");
+    }
+
+    for java in javas {
+        checks.push(check_java(java));
+    }
+
+    checks.push(check_runners_writable());
+    checks.push(check_disk_space(&templates, runner_count));
+
+    checks
+}
+
+// Async wrapper function so the GUI doesn't freeze up while java starts
+pub async fn run_diagnostics_async_wrapper(runner_count: u32) -> Vec<DiagnosticCheck> {
+    run_diagnostics(runner_count)
+}
+
+pub fn view_checklist<'a>(checks: &[DiagnosticCheck]) -> Column<'a, Message> {
+    checks.iter().fold(Column::new().spacing(4), |column, check| {
+        let (label, color) = match check.status {
+            CheckStatus::Passed => ("[OK]", Color::from_rgb(0.2, 0.6, 0.3)),
+            CheckStatus::Warning => ("[WARN]", Color::from_rgb(0.8, 0.6, 0.1)),
+            CheckStatus::Failed => ("[FAIL]", Color::from_rgb(0.8, 0.2, 0.2)),
+        };
+
+        column.push(
+            Row::new()
+                .spacing(8)
+                .push(Text::new(label).color(color).size(16).width(Length::Units(60)))
+                .push(Text::new(check.name.clone()).size(16).width(Length::Units(240)))
+                .push(Text::new(check.detail.clone()).size(16)),
+        )
+    })
+}
@@ -0,0 +1,458 @@
+use std::fs;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::sync::Arc;
+
+use parquet::basic::Compression;
+use parquet::data_type::{ByteArray, ByteArrayType, DataType, Int64Type};
+use parquet::errors::ParquetError;
+use parquet::file::properties::WriterProperties;
+use parquet::file::writer::{SerializedFileWriter, SerializedRowGroupWriter};
+use parquet::schema::parser::parse_message_type;
+use serde::Serialize;
+
+use crate::resultsdb::{self, ResultFilter, ResultsDb, StoredResult};
+use crate::seedresult::{BiomeSample, StructureSample};
+
+const CSV_HEADER: &str =
+    "seed,minecraft_version,searched_at,spawn_x,spawn_y,spawn_z,sample,name,x,y,z";
+
+// The same columns as the CSV export
+const PARQUET_SCHEMA: &str = "
+    message sample {
+        REQUIRED BYTE_ARRAY seed (UTF8);
+        OPTIONAL BYTE_ARRAY minecraft_version (UTF8);
+        REQUIRED INT64 searched_at (TIMESTAMP_MILLIS);
+        OPTIONAL INT64 spawn_x;
+        OPTIONAL INT64 spawn_y;
+        OPTIONAL INT64 spawn_z;
+        REQUIRED BYTE_ARRAY sample (UTF8);
+        REQUIRED BYTE_ARRAY name (UTF8);
+        OPTIONAL INT64 x;
+        OPTIONAL INT64 y;
+        OPTIONAL INT64 z;
+    }
+";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExportFormat {
+    // One row per structure or biome sample
+    Csv,
+    // One object per seed
+    JsonLines,
+    // The same rows as the CSV
+    Parquet,
+}
+
+impl ExportFormat {
+    pub fn from_name(name: &str) -> Option<ExportFormat> {
+        match name {
+            "csv" => Some(ExportFormat::Csv),
+            "jsonl" | "json-lines" => Some(ExportFormat::JsonLines),
+            "parquet" => Some(ExportFormat::Parquet),
+            _ => None,
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::JsonLines => "jsonl",
+            ExportFormat::Parquet => "parquet",
+        }
+    }
+}
+
+// A structure or biome sample, along with the seed it came from. Seeds without any samples
+// still get a row of their own, with a sample of "none" and an empty name, so that every
+// exported seed shows up.
+struct SampleRow<'a> {
+    result: &'a StoredResult,
+    sample: &'static str,
+    name: &'a str,
+    x: Option<i64>,
+    y: Option<i64>,
+    z: Option<i64>,
+}
+
+fn sample_rows(results: &[StoredResult]) -> Vec<SampleRow<'_>> {
+    let mut rows = vec![];
+
+    for result in results {
+        for structure in &result.structures {
+            rows.push(SampleRow {
+                result,
+                sample: "structure",
+                name: &structure.name,
+                x: structure.position.map(|(x, _, _)| x),
+                y: structure.position.map(|(_, y, _)| y),
+                z: structure.position.map(|(_, _, z)| z),
+            });
+        }
+
+        for biome in &result.biomes {
+            rows.push(SampleRow {
+                result,
+                sample: "biome",
+                name: &biome.biome,
+                x: Some(biome.x),
+                y: None,
+                z: Some(biome.z),
+            });
+        }
+
+        if result.structures.is_empty() && result.biomes.is_empty() {
+            rows.push(SampleRow {
+                result,
+                sample: "none",
+                name: "",
+                x: None,
+                y: None,
+                z: None,
+            });
+        }
+    }
+
+    rows
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn csv_number(value: Option<i64>) -> String {
+    value.map(|value| value.to_string()).unwrap_or_default()
+}
+
+fn write_csv(results: &[StoredResult], output: &mut impl Write) -> std::io::Result<()> {
+    writeln!(output, "{}", CSV_HEADER)?;
+
+    for row in sample_rows(results) {
+        let spawn = row.result.spawn;
+        writeln!(
+            output,
+            "{},{},{},{},{},{},{},{},{},{},{}",
+            csv_field(&row.result.seed),
+            csv_field(row.result.minecraft_version.as_deref().unwrap_or_default()),
+            row.result.searched_at_utc,
+            csv_number(spawn.map(|(x, _, _)| x)),
+            csv_number(spawn.map(|(_, y, _)| y)),
+            csv_number(spawn.map(|(_, _, z)| z)),
+            row.sample,
+            csv_field(row.name),
+            csv_number(row.x),
+            csv_number(row.y),
+            csv_number(row.z),
+        )?;
+    }
+
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct SeedRecord<'a> {
+    seed: &'a str,
+    minecraft_version: Option<&'a str>,
+    searched_at: &'a str,
+    spawn: Option<(i64, i64, i64)>,
+    structures: &'a [StructureSample],
+    biomes: &'a [BiomeSample],
+}
+
+fn write_json_lines(results: &[StoredResult], output: &mut impl Write) -> std::io::Result<()> {
+    for result in results {
+        let record = SeedRecord {
+            seed: &result.seed,
+            minecraft_version: result.minecraft_version.as_deref(),
+            searched_at: &result.searched_at_utc,
+            spawn: result.spawn,
+            structures: &result.structures,
+            biomes: &result.biomes,
+        };
+
+        serde_json::to_writer(&mut *output, &record)?;
+        writeln!(output)?;
+    }
+
+    Ok(())
+}
+
+// Whether a column is OPTIONAL in PARQUET_SCHEMA, and so needs definition levels
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Repetition {
+    Required,
+    Optional,
+}
+
+// Writes the next column of the row group. Missing values are left out of the column
+// itself, and only show up in its definition levels.
+fn write_parquet_column<T: DataType>(
+    row_group: &mut SerializedRowGroupWriter<'_, fs::File>,
+    repetition: Repetition,
+    values: Vec<Option<T::T>>,
+) -> Result<(), ParquetError> {
+    let mut column = row_group
+        .next_column()?
+        .ok_or_else(|| ParquetError::General("Ran out of columns".to_string()))?;
+
+    let definition_levels: Vec<i16> = values.iter().map(|value| value.is_some() as i16).collect();
+    let present_values: Vec<T::T> = values.into_iter().flatten().collect();
+
+    let writer = column.typed::<T>();
+    match repetition {
+        Repetition::Optional => {
+            writer.write_batch(&present_values, Some(&definition_levels), None)?;
+        }
+        Repetition::Required => {
+            if present_values.len() != definition_levels.len() {
+                return Err(ParquetError::General("Required column is missing values".to_string()));
+            }
+            writer.write_batch(&present_values, None, None)?;
+        }
+    };
+
+    column.close()
+}
+
+fn write_parquet(results: &[StoredResult], file: fs::File) -> Result<(), ParquetError> {
+    let schema = Arc::new(parse_message_type(PARQUET_SCHEMA)?);
+    let properties = Arc::new(
+        WriterProperties::builder()
+            .set_compression(Compression::SNAPPY)
+            .build(),
+    );
+
+    let rows = sample_rows(results);
+    let text = |value: &str| Some(ByteArray::from(value));
+
+    let mut writer = SerializedFileWriter::new(file, schema, properties)?;
+    let mut row_group = writer.next_row_group()?;
+
+    write_parquet_column::<ByteArrayType>(
+        &mut row_group,
+        Repetition::Required,
+        rows.iter().map(|row| text(&row.result.seed)).collect(),
+    )?;
+    write_parquet_column::<ByteArrayType>(
+        &mut row_group,
+        Repetition::Optional,
+        rows.iter()
+            .map(|row| row.result.minecraft_version.as_deref().and_then(text))
+            .collect(),
+    )?;
+    write_parquet_column::<Int64Type>(
+        &mut row_group,
+        Repetition::Required,
+        rows.iter().map(|row| Some(row.result.searched_at * 1000)).collect(),
+    )?;
+    write_parquet_column::<Int64Type>(
+        &mut row_group,
+        Repetition::Optional,
+        rows.iter().map(|row| row.result.spawn.map(|(x, _, _)| x)).collect(),
+    )?;
+    write_parquet_column::<Int64Type>(
+        &mut row_group,
+        Repetition::Optional,
+        rows.iter().map(|row| row.result.spawn.map(|(_, y, _)| y)).collect(),
+    )?;
+    write_parquet_column::<Int64Type>(
+        &mut row_group,
+        Repetition::Optional,
+        rows.iter().map(|row| row.result.spawn.map(|(_, _, z)| z)).collect(),
+    )?;
+    write_parquet_column::<ByteArrayType>(
+        &mut row_group,
+        Repetition::Required,
+        rows.iter().map(|row| text(row.sample)).collect(),
+    )?;
+    write_parquet_column::<ByteArrayType>(
+        &mut row_group,
+        Repetition::Required,
+        rows.iter().map(|row| text(row.name)).collect(),
+    )?;
+    write_parquet_column::<Int64Type>(
+        &mut row_group,
+        Repetition::Optional,
+        rows.iter().map(|row| row.x).collect(),
+    )?;
+    write_parquet_column::<Int64Type>(
+        &mut row_group,
+        Repetition::Optional,
+        rows.iter().map(|row| row.y).collect(),
+    )?;
+    write_parquet_column::<Int64Type>(
+        &mut row_group,
+        Repetition::Optional,
+        rows.iter().map(|row| row.z).collect(),
+    )?;
+
+    row_group.close()?;
+    writer.close()?;
+
+    Ok(())
+}
+
+// Writes out every stored result that makes it through the filter, returning how many that was
+pub fn export_results(
+    results_db: &ResultsDb,
+    filter: &ResultFilter,
+    format: ExportFormat,
+    output_path: &str,
+) -> Result<usize, String> {
+    let results = results_db.load_results(filter)?;
+
+    // Written alongside and moved into place once it's complete, so a failed export
+    // never leaves a truncated file (or clobbers an earlier export) at output_path
+    let partial_path = format!("{}.partial", output_path);
+    let file = fs::File::create(&partial_path)
+        .map_err(|e| format!("Unable to create {}: {}", partial_path, e))?;
+    let write_error = |e: String| {
+        _ = fs::remove_file(&partial_path);
+        format!("Unable to write {}: {}", output_path, e)
+    };
+
+    match format {
+        ExportFormat::Csv | ExportFormat::JsonLines => {
+            let mut output = BufWriter::new(file);
+            let written = if format == ExportFormat::Csv {
+                write_csv(&results, &mut output)
+            } else {
+                write_json_lines(&results, &mut output)
+            };
+            written
+                .and_then(|_| output.flush())
+                .map_err(|e| write_error(e.to_string()))?;
+        }
+        ExportFormat::Parquet => {
+            write_parquet(&results, file).map_err(|e| write_error(e.to_string()))?;
+        }
+    };
+
+    fs::rename(&partial_path, output_path)
+        .map_err(|e| write_error(format!("unable to move it into place: {}", e)))?;
+
+    Ok(results.len())
+}
+
+const USAGE: &str = "Usage: SpicyGarden export [--format csv|jsonl|parquet] [--output <path>] \
+                     [--since <time>] [--until <time>] [--version <minecraft version>]
+Times are UTC, given as seconds since the Unix epoch, 2022-10-01 or 2022-10-01 18:30:00.
+A date on its own in --until takes in the whole of that day.";
+
+// A bare date as the end of a range means up to the last second of that day, not its first
+fn parse_until(results_db: &ResultsDb, text: &str) -> Result<i64, String> {
+    if text.parse::<i64>().is_err() {
+        if let Ok(end_of_day) = results_db.parse_time(&format!("{} 23:59:59", text)) {
+            return Ok(end_of_day);
+        }
+    }
+
+    results_db.parse_time(text)
+}
+
+// The `export` command. The format can be left out if the output path's extension gives it away.
+pub fn run_export_command(args: &[String]) {
+    let mut format_name: Option<&str> = None;
+    let mut output_path: Option<&str> = None;
+    let mut since: Option<&str> = None;
+    let mut until: Option<&str> = None;
+    let mut minecraft_version: Option<String> = None;
+
+    let mut args = args.iter();
+    while let Some(option) = args.next() {
+        let value = match args.next() {
+            Some(value) => value.as_str(),
+            None => {
+                println!("ERROR: {} needs a value", option);
+                println!("{}", USAGE);
+                return;
+            }
+        };
+
+        match option.as_str() {
+            "--format" => format_name = Some(value),
+            "--output" => output_path = Some(value),
+            "--since" => since = Some(value),
+            "--until" => until = Some(value),
+            "--version" => minecraft_version = Some(value.to_string()),
+            _ => {
+                println!("ERROR: Unknown export option: {}", option);
+                println!("{}", USAGE);
+                return;
+            }
+        };
+    }
+
+    let format_name = format_name.or_else(|| {
+        output_path
+            .and_then(|output_path| Path::new(output_path).extension())
+            .and_then(|extension| extension.to_str())
+    });
+
+    let format = match format_name.and_then(ExportFormat::from_name) {
+        Some(format) => format,
+        None => {
+            println!("ERROR: Export format must be one of csv, jsonl or parquet");
+            println!("{}", USAGE);
+            return;
+        }
+    };
+
+    let output_path = output_path
+        .map(|output_path| output_path.to_string())
+        .unwrap_or_else(|| format!("results.{}", format.extension()));
+
+    let results_db = match ResultsDb::open(resultsdb::RESULTS_DB_PATH) {
+        Ok(results_db) => results_db,
+        Err(e) => {
+            println!("ERROR: {}", e);
+            return;
+        }
+    };
+
+    let since = since.map(|text| results_db.parse_time(text)).transpose();
+    let until = until.map(|text| parse_until(&results_db, text)).transpose();
+
+    let filter = match (since, until) {
+        (Ok(since), Ok(until)) => ResultFilter {
+            since,
+            until,
+            minecraft_version,
+        },
+        (Err(e), _) | (_, Err(e)) => {
+            println!("ERROR: {}", e);
+            return;
+        }
+    };
+
+    match export_results(&results_db, &filter, format, &output_path) {
+        Ok(count) => println!("Exported {} seeds to {}", count, output_path),
+        Err(e) => println!("ERROR: {}", e),
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn until_dates_take_in_the_whole_day() {
+        let results_db = ResultsDb::open(":memory:").unwrap();
+
+        let start_of_day = results_db.parse_time("2022-10-01").unwrap();
+        assert_eq!(start_of_day, 1664582400);
+        assert_eq!(parse_until(&results_db, "2022-10-01"), Ok(start_of_day + 24 * 60 * 60 - 1));
+
+        // Anything more precise than a date is taken as it is
+        assert_eq!(
+            parse_until(&results_db, "2022-10-01 18:30:00"),
+            Ok(start_of_day + 18 * 60 * 60 + 30 * 60)
+        );
+        assert_eq!(parse_until(&results_db, "1664582400"), Ok(start_of_day));
+        assert!(parse_until(&results_db, "yesterday").is_err());
+    }
+}
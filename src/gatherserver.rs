@@ -0,0 +1,317 @@
+use reqwest::blocking::{Client, RequestBuilder};
+use reqwest::{StatusCode, Url};
+use serde::{Deserialize, Serialize};
+
+use crate::servertemplate::{self, ReadyTemplate};
+
+pub const CLIENT_VERSION: &str = env!("CARGO_PKG_VERSION");
+pub const CLIENT_COMMIT: &str = env!("GIT_HASH");
+
+// The shapes a seed's results can be submitted in
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ResultFormat {
+    // SpicyGardenData.txt exactly as the plugin wrote it
+    Raw,
+    // Just the summary from seedresult, as JSON
+    Summary,
+    // Both, as a JSON object with "raw" and "summary" fields
+    RawWithSummary,
+}
+
+impl ResultFormat {
+    pub const ALL: [ResultFormat; 3] = [
+        ResultFormat::Raw,
+        ResultFormat::Summary,
+        ResultFormat::RawWithSummary,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ResultFormat::Raw => "raw",
+            ResultFormat::Summary => "summary",
+            ResultFormat::RawWithSummary => "raw+summary",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<ResultFormat> {
+        ResultFormat::ALL
+            .iter()
+            .find(|format| format.name() == name)
+            .copied()
+    }
+}
+
+#[derive(Serialize)]
+struct HandshakeTemplate {
+    // What seeds should ask for to be run with this template, if anything
+    name: Option<String>,
+    plugin_version: Option<String>,
+    minecraft_version: Option<String>,
+    server_jar_sha256: String,
+    plugin_sha256: String,
+}
+
+#[derive(Serialize)]
+struct HandshakeRequest {
+    client_version: String,
+    client_commit: String,
+    templates: Vec<HandshakeTemplate>,
+    result_formats: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct HandshakeResponse {
+    accepted: bool,
+    #[serde(default)]
+    reason: Option<String>,
+    #[serde(default)]
+    result_format: Option<String>,
+    // Whether the server wants each seed's biome preview image too
+    #[serde(default)]
+    accepts_previews: bool,
+}
+
+// What the gather server agreed to at the start of a search
+#[derive(Debug, Clone)]
+pub struct Handshake {
+    pub result_format: ResultFormat,
+    pub accepts_previews: bool,
+}
+
+// How a request tells the gather server which client key it's coming from
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AuthStyle {
+    // `Authorization: Bearer <key>`, which keeps the key out of URLs and server logs
+    Header,
+    // The key as a path segment, e.g. /assign_seeds/<key>/4, for older gather servers
+    UrlPath,
+}
+
+// Builds requests to the gather server, working out how to present the client key on first use
+pub struct GatherServer {
+    address: String,
+    client_key: String,
+    http_client: Client,
+    auth_style: Option<AuthStyle>,
+}
+
+impl GatherServer {
+    pub fn new(address: &str, client_key: &str) -> GatherServer {
+        GatherServer {
+            address: address.trim_end_matches('/').to_string(),
+            client_key: client_key.to_string(),
+            http_client: Client::new(),
+            auth_style: None,
+        }
+    }
+
+    // Servers that understand the Authorization header serve /my_stats without a
+    // key in the path. Older ones don't have that route at all, and 404.
+    fn negotiate_auth_style(&mut self) -> Result<AuthStyle, reqwest::Error> {
+        if let Some(auth_style) = self.auth_style {
+            return Ok(auth_style);
+        }
+
+        let response = self
+            .http_client
+            .get(format!("{}/my_stats", self.address))
+            .bearer_auth(&self.client_key)
+            .send()?;
+
+        let auth_style = match response.status() {
+            StatusCode::NOT_FOUND | StatusCode::METHOD_NOT_ALLOWED => AuthStyle::UrlPath,
+            _ => AuthStyle::Header,
+        };
+
+        self.auth_style = Some(auth_style);
+        Ok(auth_style)
+    }
+
+    fn url(&self, auth_style: AuthStyle, endpoint: &str, path: &[&str]) -> String {
+        let mut url = format!("{}/{}", self.address, endpoint);
+
+        if auth_style == AuthStyle::UrlPath {
+            url.push('/');
+            url.push_str(&self.client_key);
+        }
+
+        for segment in path {
+            url.push('/');
+            url.push_str(segment);
+        }
+
+        url
+    }
+
+    fn authorize(&self, auth_style: AuthStyle, request: RequestBuilder) -> RequestBuilder {
+        match auth_style {
+            AuthStyle::Header => request.bearer_auth(&self.client_key),
+            AuthStyle::UrlPath => request,
+        }
+    }
+
+    // Starts a GET request to `endpoint`, with any further `path` segments after the key
+    pub fn get(&mut self, endpoint: &str, path: &[&str]) -> Result<RequestBuilder, reqwest::Error> {
+        let auth_style = self.negotiate_auth_style()?;
+        let request = self.http_client.get(self.url(auth_style, endpoint, path));
+        Ok(self.authorize(auth_style, request))
+    }
+
+    // Starts a POST request to `endpoint`, with any further `path` segments after the key
+    pub fn post(&mut self, endpoint: &str, path: &[&str]) -> Result<RequestBuilder, reqwest::Error> {
+        let auth_style = self.negotiate_auth_style()?;
+        let request = self.http_client.post(self.url(auth_style, endpoint, path));
+        Ok(self.authorize(auth_style, request))
+    }
+
+    // Makes sure the gather server can be reached and accepts our client key,
+    // so that problems show up before any runners start rather than in a retry loop
+    pub fn check_connection(&mut self) -> Result<(), String> {
+        let url = Url::parse(&self.address)
+            .map_err(|e| format!("Server address {:?} isn't a valid URL: {}", self.address, e))?;
+
+        if url.scheme() != "http" && url.scheme() != "https" {
+            return Err(format!(
+                "Server address {:?} must start with http:// or https://",
+                self.address
+            ));
+        }
+
+        let health_response = self
+            .http_client
+            .get(format!("{}/health", self.address))
+            .send()
+            .map_err(|e| format!("Unable to reach gather server at {}: {}", self.address, e))?;
+
+        match health_response.status() {
+            StatusCode::OK => (),
+            // Older gather servers only have the seed routes, which get checked below
+            StatusCode::NOT_FOUND => println!(
+                "{} has no /health endpoint, so it's probably an older gather server",
+                self.address
+            ),
+            status => return Err(format!("Gather server isn't healthy: responded with {}", status)),
+        };
+
+        let key_response = self
+            .get("my_stats", &[])
+            .and_then(|request| request.send())
+            .map_err(|e| format!("Unable to reach gather server at {}: {}", self.address, e))?;
+
+        match key_response.status() {
+            StatusCode::OK => Ok(()),
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => {
+                Err("Gather server rejected the client key".to_string())
+            }
+            StatusCode::NOT_FOUND => self.check_legacy_connection(),
+            status => Err(format!("Unexpected response while checking client key: {}", status)),
+        }
+    }
+
+    // Older gather servers have no /my_stats, but asking them for no seeds at all
+    // still checks the key without taking any seeds out of circulation
+    fn check_legacy_connection(&mut self) -> Result<(), String> {
+        let key_response = self
+            .get("assign_seeds", &["0"])
+            .and_then(|request| request.send())
+            .map_err(|e| format!("Unable to reach gather server at {}: {}", self.address, e))?;
+
+        match key_response.status() {
+            StatusCode::OK => Ok(()),
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => {
+                Err("Gather server rejected the client key".to_string())
+            }
+            StatusCode::NOT_FOUND => Err(format!(
+                "{} doesn't look like a SpicyGarden gather server",
+                self.address
+            )),
+            status => Err(format!("Unexpected response while checking client key: {}", status)),
+        }
+    }
+
+    // Tells the gather server what's about to be searching for it. The server can
+    // refuse clients it considers outdated, or pick which result format it wants.
+    pub fn handshake(&mut self, templates: &[ReadyTemplate]) -> Result<Handshake, String> {
+        let handshake_request = HandshakeRequest {
+            client_version: CLIENT_VERSION.to_string(),
+            client_commit: CLIENT_COMMIT.trim().to_string(),
+            templates: templates
+                .iter()
+                .map(|ready| HandshakeTemplate {
+                    name: ready.template.name.clone(),
+                    plugin_version: ready.fingerprint.plugin_version.clone(),
+                    minecraft_version: ready.fingerprint.minecraft_version.clone(),
+                    server_jar_sha256: ready.fingerprint.server_jar_sha256.clone(),
+                    plugin_sha256: ready.fingerprint.plugin_sha256.clone(),
+                })
+                .collect(),
+            result_formats: ResultFormat::ALL
+                .iter()
+                .map(|format| format.name().to_string())
+                .collect(),
+        };
+
+        let response = self
+            .post("handshake", &[])
+            .and_then(|request| request.json(&handshake_request).send())
+            .map_err(|e| format!("Unable to reach gather server at {}: {}", self.address, e))?;
+
+        let status = response.status();
+
+        if status == StatusCode::NOT_FOUND {
+            // Older gather servers don't handshake, and only understand raw results
+            return Ok(Handshake {
+                result_format: ResultFormat::Raw,
+                accepts_previews: false,
+            });
+        }
+
+        let reply = response.json::<HandshakeResponse>().map_err(|e| {
+            format!(
+                "Unable to understand gather server's handshake ({}): {}",
+                status, e
+            )
+        })?;
+
+        if !reply.accepted {
+            return Err(format!(
+                "Gather server refused this client: {}",
+                reply.reason.unwrap_or_else(|| "no reason given".to_string())
+            ));
+        }
+
+        let result_format = match reply.result_format {
+            None => ResultFormat::Raw,
+            Some(name) => ResultFormat::from_name(&name).ok_or_else(|| {
+                format!(
+                    "Gather server asked for {:?} results, which this client can't produce",
+                    name
+                )
+            })?,
+        };
+
+        Ok(Handshake {
+            result_format,
+            accepts_previews: reply.accepts_previews,
+        })
+    }
+}
+
+// What's left to agree with the gather server once the connection has been checked
+// and the templates synced, right before a search starts
+pub async fn preflight_async_wrapper(
+    address: String,
+    client_key: String,
+) -> Result<(Handshake, Vec<ReadyTemplate>), String> {
+    let mut gather_server = GatherServer::new(&address, &client_key);
+
+    let templates = servertemplate::prepare_templates()?;
+    let handshake = gather_server.handshake(&templates)?;
+
+    Ok((handshake, templates))
+}
+
+// Async wrapper function so the GUI can check the connection without freezing up
+pub async fn check_connection_async_wrapper(address: String, client_key: String) -> Result<(), String> {
+    GatherServer::new(&address, &client_key).check_connection()
+}
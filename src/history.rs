@@ -0,0 +1,199 @@
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+use crate::runner::RunnerEvent;
+
+// How far back the charts look
+const HISTORY_WINDOW: Duration = Duration::from_secs(60 * 60);
+
+// Throughput and backlog are charted per minute, outcome rates per five minutes
+pub const THROUGHPUT_BUCKET_COUNT: usize = 60;
+pub const OUTCOME_BUCKET_COUNT: usize = 12;
+
+// Seed durations are binned five seconds at a time, with the last bin catching everything slower
+pub const DURATION_BIN_SECONDS: u64 = 5;
+pub const DURATION_BIN_COUNT: usize = 13;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum FinishKind {
+    Completed,
+    Failed,
+    TimedOut,
+}
+
+struct FinishedSeed {
+    finished_at: Instant,
+    duration: Duration,
+    kind: FinishKind,
+}
+
+// A rolling record of what a search has been up to, for charting
+#[derive(Default)]
+pub struct SearchHistory {
+    runner_started_at: HashMap<u32, Instant>,
+    finished_seeds: VecDeque<FinishedSeed>,
+    submitted_at: VecDeque<Instant>,
+    backlog_samples: VecDeque<(Instant, u32)>,
+    pending_submissions: u32,
+}
+
+impl SearchHistory {
+    pub fn record_event(&mut self, event: &RunnerEvent) {
+        let now = Instant::now();
+
+        match event {
+            RunnerEvent::RunnerStarted { runner_index, .. } => {
+                self.runner_started_at.insert(*runner_index, now);
+            }
+            RunnerEvent::SeedCompleted { runner_index, .. } => {
+                self.finish_seed(*runner_index, FinishKind::Completed, now);
+                self.pending_submissions += 1;
+                self.backlog_samples.push_back((now, self.pending_submissions));
+            }
+            RunnerEvent::SeedFailed { runner_index, .. } => {
+                self.finish_seed(*runner_index, FinishKind::Failed, now);
+            }
+            RunnerEvent::SeedTimedOut { runner_index, .. } => {
+                self.finish_seed(*runner_index, FinishKind::TimedOut, now);
+            }
+            RunnerEvent::SubmissionSucceeded { .. } => {
+                self.submitted_at.push_back(now);
+                self.pending_submissions = self.pending_submissions.saturating_sub(1);
+                self.backlog_samples.push_back((now, self.pending_submissions));
+            }
+            _ => (),
+        };
+
+        self.prune(now);
+    }
+
+    pub fn pending_submissions(&self) -> u32 {
+        self.pending_submissions
+    }
+
+    fn finish_seed(&mut self, runner_index: u32, kind: FinishKind, now: Instant) {
+        let started_at = match self.runner_started_at.remove(&runner_index) {
+            Some(started_at) => started_at,
+            None => return,
+        };
+
+        self.finished_seeds.push_back(FinishedSeed {
+            finished_at: now,
+            duration: now.duration_since(started_at),
+            kind,
+        });
+    }
+
+    // Forget anything that has fallen out of the window
+    fn prune(&mut self, now: Instant) {
+        while let Some(seed) = self.finished_seeds.front() {
+            if now.duration_since(seed.finished_at) <= HISTORY_WINDOW {
+                break;
+            }
+            self.finished_seeds.pop_front();
+        }
+
+        while let Some(submitted_at) = self.submitted_at.front() {
+            if now.duration_since(*submitted_at) <= HISTORY_WINDOW {
+                break;
+            }
+            self.submitted_at.pop_front();
+        }
+
+        while let Some((sampled_at, _)) = self.backlog_samples.front() {
+            if now.duration_since(*sampled_at) <= HISTORY_WINDOW {
+                break;
+            }
+            self.backlog_samples.pop_front();
+        }
+    }
+
+    // Which of `bucket_count` equal buckets spanning the window a moment falls into, oldest first
+    fn bucket_index(now: Instant, moment: Instant, bucket_count: usize) -> Option<usize> {
+        let age = now.saturating_duration_since(moment);
+        if age >= HISTORY_WINDOW {
+            return None;
+        }
+
+        let bucket_width = HISTORY_WINDOW.as_secs_f32() / bucket_count as f32;
+        let buckets_ago = (age.as_secs_f32() / bucket_width) as usize;
+        Some(bucket_count - 1 - buckets_ago.min(bucket_count - 1))
+    }
+
+    // Seeds submitted during each minute of the last hour, oldest first
+    pub fn throughput_per_minute(&self, now: Instant) -> Vec<f32> {
+        let mut buckets = vec![0.0; THROUGHPUT_BUCKET_COUNT];
+
+        for submitted_at in &self.submitted_at {
+            if let Some(index) = Self::bucket_index(now, *submitted_at, THROUGHPUT_BUCKET_COUNT) {
+                buckets[index] += 1.0;
+            }
+        }
+
+        buckets
+    }
+
+    // How many seeds took how long, successful or not
+    pub fn duration_histogram(&self) -> Vec<f32> {
+        let mut bins = vec![0.0; DURATION_BIN_COUNT];
+
+        for seed in &self.finished_seeds {
+            let bin = (seed.duration.as_secs() / DURATION_BIN_SECONDS) as usize;
+            bins[bin.min(DURATION_BIN_COUNT - 1)] += 1.0;
+        }
+
+        bins
+    }
+
+    // Percentage of finished seeds that failed, and that timed out, during each bucket
+    pub fn outcome_rates(&self, now: Instant) -> (Vec<f32>, Vec<f32>) {
+        let mut totals = [0.0; OUTCOME_BUCKET_COUNT];
+        let mut failures = vec![0.0; OUTCOME_BUCKET_COUNT];
+        let mut timeouts = vec![0.0; OUTCOME_BUCKET_COUNT];
+
+        for seed in &self.finished_seeds {
+            if let Some(index) = Self::bucket_index(now, seed.finished_at, OUTCOME_BUCKET_COUNT) {
+                totals[index] += 1.0;
+                match seed.kind {
+                    FinishKind::Failed => failures[index] += 1.0,
+                    FinishKind::TimedOut => timeouts[index] += 1.0,
+                    FinishKind::Completed => (),
+                };
+            }
+        }
+
+        let as_percentages = |counts: Vec<f32>| -> Vec<f32> {
+            counts
+                .iter()
+                .zip(totals.iter())
+                .map(|(count, total)| if *total > 0.0 { count / total * 100.0 } else { 0.0 })
+                .collect()
+        };
+
+        (as_percentages(failures), as_percentages(timeouts))
+    }
+
+    // The largest submission backlog seen during each minute of the last hour, oldest first.
+    // Minutes without any change carry the previous value forward.
+    pub fn backlog_per_minute(&self, now: Instant) -> Vec<f32> {
+        let mut buckets: Vec<Option<f32>> = vec![None; THROUGHPUT_BUCKET_COUNT];
+
+        for (sampled_at, backlog) in &self.backlog_samples {
+            if let Some(index) = Self::bucket_index(now, *sampled_at, THROUGHPUT_BUCKET_COUNT) {
+                let backlog = *backlog as f32;
+                buckets[index] = Some(buckets[index].map_or(backlog, |known| known.max(backlog)));
+            }
+        }
+
+        let mut carried = 0.0;
+        buckets
+            .into_iter()
+            .map(|bucket| {
+                if let Some(backlog) = bucket {
+                    carried = backlog;
+                }
+                carried
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,171 @@
+use crate::nbt::{self, Tag};
+
+// What a generated world's level.dat says about it
+#[derive(Debug, Clone, Default)]
+pub struct LevelInfo {
+    pub level_name: Option<String>,
+    pub data_version: Option<i64>,
+    pub spawn: Option<(i64, i64, i64)>,
+    // Something like "minecraft:noise minecraft:overworld", or "default" on older versions
+    pub generator: Option<String>,
+    pub seed: Option<i64>,
+}
+
+// Since 1.16, the overworld's generator lives under WorldGenSettings. Before that it was
+// just a name (and options string) at the top level.
+fn read_generator(data: &Tag) -> Option<String> {
+    if let Some(generator) =
+        data.path(&["WorldGenSettings", "dimensions", "minecraft:overworld", "generator"])
+    {
+        let generator_type = generator.get("type").and_then(Tag::as_str)?;
+
+        return Some(match generator.get("settings").and_then(Tag::as_str) {
+            Some(settings) => format!("{} {}", generator_type, settings),
+            None => generator_type.to_string(),
+        });
+    }
+
+    data.get("generatorName")
+        .and_then(Tag::as_str)
+        .map(|name| name.to_string())
+}
+
+pub fn read_level_info(world_dir: &str) -> Result<LevelInfo, String> {
+    let root = nbt::read_gzip_file(&format!("{}/level.dat", world_dir))?;
+    let data = root.get("Data").ok_or("level.dat has no Data compound")?;
+
+    let spawn_coordinate = |key: &str| data.get(key).and_then(Tag::as_i64);
+
+    let spawn = match (
+        spawn_coordinate("SpawnX"),
+        spawn_coordinate("SpawnY"),
+        spawn_coordinate("SpawnZ"),
+    ) {
+        (Some(x), Some(y), Some(z)) => Some((x, y, z)),
+        _ => None,
+    };
+
+    Ok(LevelInfo {
+        level_name: data.get("LevelName").and_then(Tag::as_str).map(|name| name.to_string()),
+        data_version: data.get("DataVersion").and_then(Tag::as_i64),
+        spawn,
+        generator: read_generator(data),
+        seed: data
+            .path(&["WorldGenSettings", "seed"])
+            .or_else(|| data.get("RandomSeed"))
+            .and_then(Tag::as_i64),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fs;
+    use std::io::Write;
+
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+
+    fn entry(tag_type: u8, name: &str, payload: &[u8]) -> Vec<u8> {
+        let mut data = vec![tag_type];
+        data.extend_from_slice(&(name.len() as u16).to_be_bytes());
+        data.extend_from_slice(name.as_bytes());
+        data.extend_from_slice(payload);
+        data
+    }
+
+    fn string(value: &str) -> Vec<u8> {
+        let mut data = (value.len() as u16).to_be_bytes().to_vec();
+        data.extend_from_slice(value.as_bytes());
+        data
+    }
+
+    // A 1.18-style level.dat, trimmed down to what read_level_info looks at
+    fn level_dat() -> Vec<u8> {
+        let mut generator = entry(8, "type", &string("minecraft:noise"));
+        generator.extend(entry(8, "settings", &string("minecraft:overworld")));
+        generator.push(0);
+
+        let mut overworld = entry(10, "generator", &generator);
+        overworld.push(0);
+
+        let mut dimensions = entry(10, "minecraft:overworld", &overworld);
+        dimensions.push(0);
+
+        let mut world_gen_settings = entry(4, "seed", &(-4172144997902289642i64).to_be_bytes());
+        world_gen_settings.extend(entry(10, "dimensions", &dimensions));
+        world_gen_settings.push(0);
+
+        let mut data = entry(8, "LevelName", &string("world"));
+        data.extend(entry(3, "DataVersion", &2975i32.to_be_bytes()));
+        data.extend(entry(3, "SpawnX", &(-48i32).to_be_bytes()));
+        data.extend(entry(3, "SpawnY", &71i32.to_be_bytes()));
+        data.extend(entry(3, "SpawnZ", &160i32.to_be_bytes()));
+        data.extend(entry(10, "WorldGenSettings", &world_gen_settings));
+        data.push(0);
+
+        let mut root = entry(10, "", &entry(10, "Data", &data));
+        root.push(0);
+        root
+    }
+
+    // Each test gets its own world directory, since tests run in parallel
+    fn write_world(name: &str, level_dat: &[u8]) -> String {
+        let world_dir = std::env::temp_dir()
+            .join(format!("leveldat-test-{}-{}", std::process::id(), name))
+            .to_string_lossy()
+            .to_string();
+        fs::create_dir_all(&world_dir).unwrap();
+
+        let mut encoder = GzEncoder::new(vec![], Compression::default());
+        encoder.write_all(level_dat).unwrap();
+        fs::write(format!("{}/level.dat", world_dir), encoder.finish().unwrap()).unwrap();
+
+        world_dir
+    }
+
+    #[test]
+    fn level_info_is_read_out() {
+        let world_dir = write_world("good", &level_dat());
+        let info = read_level_info(&world_dir);
+        fs::remove_dir_all(&world_dir).unwrap();
+
+        let info = info.unwrap();
+        assert_eq!(info.level_name.as_deref(), Some("world"));
+        assert_eq!(info.data_version, Some(2975));
+        assert_eq!(info.spawn, Some((-48, 71, 160)));
+        assert_eq!(
+            info.generator.as_deref(),
+            Some("minecraft:noise minecraft:overworld")
+        );
+        assert_eq!(info.seed, Some(-4172144997902289642));
+    }
+
+    #[test]
+    fn truncated_level_dat_is_an_error() {
+        let level_dat = level_dat();
+        let world_dir = write_world("truncated", &level_dat[..level_dat.len() / 2]);
+        let info = read_level_info(&world_dir);
+        fs::remove_dir_all(&world_dir).unwrap();
+
+        assert!(info.is_err());
+    }
+
+    #[test]
+    fn oversized_lengths_are_an_error() {
+        let mut list = vec![4];
+        list.extend_from_slice(&i32::MAX.to_be_bytes());
+
+        let mut data = entry(9, "ServerBrands", &list);
+        data.push(0);
+        let mut root = entry(10, "", &entry(10, "Data", &data));
+        root.push(0);
+
+        let world_dir = write_world("oversized", &root);
+        let info = read_level_info(&world_dir);
+        fs::remove_dir_all(&world_dir).unwrap();
+
+        assert!(info.is_err());
+    }
+}
@@ -1,65 +1,139 @@
-use std::fs;
-use std::sync::atomic;
+use std::sync::mpsc;
 use std::thread;
 use std::time::{Duration, Instant};
 
 use iced::{
-    executor, Application, Button, Column, Element, Padding, Row, Settings, Subscription, Text,
-    TextInput,
+    executor, Application, Button, Column, Element, Padding, PickList, Row, Scrollable, Settings,
+    Subscription, Text, TextInput,
 };
 extern crate iced_native;
 
 extern crate fs_extra;
 
-use serde::Deserialize;
-
+mod anvil;
+mod charts;
+mod config;
+mod credentials;
+mod diagnostics;
+mod export;
+mod gatherserver;
+mod history;
+mod leveldat;
+mod nbt;
+mod preview;
+mod resultsdb;
+mod rules;
 mod runner;
+mod runnerstatus;
+mod seedresult;
+mod servertemplate;
+mod stats;
+mod terrain;
+mod update;
 
 #[derive(Debug, Clone, PartialEq)]
 enum RunningState {
     Waiting,
+    RunningPreflight,
     Running,
+    Paused,
+    Stopping,
     Quitting,
     Quit,
 }
 
 struct SpicyGarden {
+    scroll: iced::scrollable::State,
+
     start_button: iced::button::State,
+    save_button: iced::button::State,
+    test_connection_button: iced::button::State,
+    check_environment_button: iced::button::State,
+    pause_button: iced::button::State,
+    stop_button: iced::button::State,
+    download_update_button: iced::button::State,
 
     server_address_input: iced::text_input::State,
     server_address: String,
 
     client_key_input: iced::text_input::State,
     client_key: String,
+    client_key_storage: credentials::KeyStorage,
+    client_key_revealed: bool,
+    reveal_key_button: iced::button::State,
+    client_key_storage_list: iced::pick_list::State<credentials::KeyStorage>,
 
     runner_count_input: iced::text_input::State,
     runner_count: String,
 
+    config_path: String,
+    // What the config file itself says, and what overrode it, so saving leaves overrides out
+    file_config: config::Config,
+    overrides: config::ConfigOverrides,
+    store_results: bool,
+    rules: Vec<rules::Rule>,
+
     status_message: String,
     running_state: RunningState,
+    diagnostics: Vec<diagnostics::DiagnosticCheck>,
+
+    available_update: Option<update::UpdateInfo>,
+    update_status: String,
 
     searched_seed_count: u32,
     started_running_at: Option<Instant>,
+    runner_statuses: Vec<runnerstatus::RunnerStatus>,
+    search_history: history::SearchHistory,
+    // Newest first, at most preview::RECENT_PREVIEW_COUNT of them
+    recent_previews: Vec<preview::BiomePreview>,
+    // Newest first, at most rules::RECENT_FIND_COUNT of them
+    recent_finds: Vec<rules::Find>,
+    recent_events: Vec<String>,
+
+    lifetime_stats: stats::LifetimeStats,
+    search_client_key: String,
+    runtime_accounted_at: Option<Instant>,
+
+    runner: Option<runner::RunnerHandle>,
 }
 
 #[derive(Debug, Clone)]
 enum Message {
     StartSeedSearch,
-    StoppedSeedSearch,
-    SearchedSeedCountUpdated(u32),
+    ConnectionChecked(u32, Result<(), String>),
+    TemplateSynced(u32, Result<String, String>),
+    PreflightFinished(
+        u32,
+        Result<(gatherserver::Handshake, Vec<servertemplate::ReadyTemplate>), String>,
+    ),
+    CheckEnvironment,
+    DiagnosticsFinished(Option<u32>, Vec<diagnostics::DiagnosticCheck>),
+    TestConnection,
+    ConnectionTested(Result<(), String>),
+    SaveSettings,
+    UpdateChecked(Result<Option<update::UpdateInfo>, String>),
+    DownloadUpdate,
+    UpdateDownloaded(Result<String, String>),
+    PauseSeedSearch,
+    ResumeSeedSearch,
+    StopSeedSearch,
+    RunnerEventReceived(u64, runner::RunnerEvent),
     ServerAddressChanged(String),
     ClientKeyChanged(String),
+    ToggleClientKeyRevealed,
+    ClientKeyStorageChanged(credentials::KeyStorage),
     RunnerCountChanged(String),
     Quit,
     IgnorableEvent,
 }
 
-#[derive(Deserialize)]
 #[derive(Default)]
 struct SpicyGardenFlags {
-    server_address: String,
-    client_key: String,
-    runner_count: u32,
+    config: config::Config,
+    file_config: config::Config,
+    overrides: config::ConfigOverrides,
+    config_path: String,
+    config_errors: Vec<config::ConfigError>,
 }
 
 impl Application for SpicyGarden {
@@ -68,26 +142,75 @@ impl Application for SpicyGarden {
     type Flags = SpicyGardenFlags;
 
     fn new(flags: SpicyGardenFlags) -> (SpicyGarden, iced::Command<Self::Message>) {
+        // Anything wrong with the config should be the first thing the user sees
+        let status_message = flags
+            .config_errors
+            .iter()
+            .map(|error| format!("ERROR: {} {}", flags.config_path, error))
+            .collect::<Vec<String>>()
+            .join("\n");
+
+        let check_for_update = iced::Command::perform(
+            update::check_for_update_async_wrapper(
+                flags.config.server_address.clone(),
+                flags.config.client_key.clone(),
+            ),
+            Message::UpdateChecked,
+        );
+
         (
             SpicyGarden {
+                scroll: iced::scrollable::State::new(),
+
                 start_button: iced::button::State::new(),
+                save_button: iced::button::State::new(),
+                test_connection_button: iced::button::State::new(),
+                check_environment_button: iced::button::State::new(),
+                pause_button: iced::button::State::new(),
+                stop_button: iced::button::State::new(),
+                download_update_button: iced::button::State::new(),
 
                 server_address_input: iced::text_input::State::new(),
-                server_address: flags.server_address,
+                server_address: flags.config.server_address,
 
                 client_key_input: iced::text_input::State::new(),
-                client_key: flags.client_key,
+                client_key: flags.config.client_key,
+                client_key_storage: flags.config.client_key_storage,
+                client_key_revealed: false,
+                reveal_key_button: iced::button::State::new(),
+                client_key_storage_list: iced::pick_list::State::new(),
 
                 runner_count_input: iced::text_input::State::new(),
-                runner_count: flags.runner_count.to_string(),
+                runner_count: flags.config.runner_count.to_string(),
+
+                config_path: flags.config_path,
+                file_config: flags.file_config,
+                overrides: flags.overrides,
+                store_results: flags.config.store_results,
+                rules: flags.config.rules,
 
-                status_message: "".to_string(),
+                status_message,
                 running_state: RunningState::Waiting,
+                diagnostics: vec![],
+
+                available_update: None,
+                update_status: "".to_string(),
 
                 searched_seed_count: 0,
                 started_running_at: None,
+                runner_statuses: vec![],
+                search_history: history::SearchHistory::default(),
+                recent_previews: vec![],
+                recent_finds: vec![],
+                recent_events: vec![],
+
+                lifetime_stats: stats::LifetimeStats::load(),
+                search_client_key: "".to_string(),
+                runtime_accounted_at: None,
+
+                runner: None,
             },
-            iced::Command::none(),
+            check_for_update,
         )
     }
 
@@ -96,38 +219,56 @@ impl Application for SpicyGarden {
     }
 
     fn subscription(&self) -> Subscription<Message> {
-        let subscriptions: Vec<Subscription<Message>> = vec![
+        let mut subscriptions: Vec<Subscription<Message>> = vec![
             iced_native::subscription::events().map(|event| match event {
-                iced_native::Event::Window(window_event) => match window_event {
-                    iced_native::window::Event::CloseRequested => Message::Quit,
-                    _ => Message::IgnorableEvent,
-                },
+                iced_native::Event::Window(iced_native::window::Event::CloseRequested) => Message::Quit,
                 _ => Message::IgnorableEvent,
             }),
-            iced_native::subscription::unfold((), self.searched_seed_count.clone(), |known_seed_count| async move {
-                // NOTE: Because rust standard library doesn't have unbounded channels
-                // that would let me bring a reference of a mpsc Receiver into a
-                // closure like this, and I don't want to use Tokio, we're doing
-                // this funny caricature of a busy loop to watch for when the
-                // searched seed count updates.
-                // Ain't I a stinker?
+        ];
 
-                thread::sleep(Duration::from_millis(100));
+        if let Some(handle) = &self.runner {
+            let search_id = handle.id();
+
+            subscriptions.push(iced_native::subscription::unfold(
+                search_id,
+                handle.event_receiver(),
+                move |event_rx| async move {
+                    // Blocking is fine here since the executor runs us on a thread pool,
+                    // but only block for a moment at a time so that a pool thread isn't
+                    // tied up forever once the search goes away.
+                    let received = event_rx.lock().unwrap().recv_timeout(Duration::from_millis(100));
+
+                    match received {
+                        Ok(event) => (Some(Message::RunnerEventReceived(search_id, event)), event_rx),
+                        // Keep the view ticking over so uptime and elapsed times stay current
+                        Err(mpsc::RecvTimeoutError::Timeout) => (Some(Message::IgnorableEvent), event_rx),
+                        Err(mpsc::RecvTimeoutError::Disconnected) => {
+                            thread::sleep(Duration::from_millis(100));
+                            (None, event_rx)
+                        }
+                    }
+                },
+            ));
+        };
 
-                let current_seed_count: u32 = runner::JAVA_SEED_SEARCH_COUNT.load(atomic::Ordering::Relaxed);
+        Subscription::batch(subscriptions)
+    }
 
-                if current_seed_count != known_seed_count {
-                    return (Some(Message::SearchedSeedCountUpdated(current_seed_count)), current_seed_count);
-                };
+    fn view(&mut self) -> Element<'_, Self::Message> {
+        let mut client_key_field = TextInput::new(
+            &mut self.client_key_input,
+            "super_secret_key",
+            &self.client_key,
+            Message::ClientKeyChanged,
+        )
+        .padding(Padding::from(8));
 
-                (Some(Message::IgnorableEvent), known_seed_count)
-            }),
-        ];
+        if !self.client_key_revealed {
+            client_key_field = client_key_field.password();
+        };
 
-        Subscription::batch(subscriptions.into_iter())
-    }
+        let reveal_key_label = if self.client_key_revealed { "Hide" } else { "Show" };
 
-    fn view(&mut self) -> Element<Self::Message> {
         let mut column =
             Column::new().push(Row::new().push(Text::new("SpicyGarden by Wilm & Jesse").size(32)));
 
@@ -151,14 +292,26 @@ impl Application for SpicyGarden {
                 .push(
                     Row::new()
                         .push(Text::new("Client key:"))
+                        .push(client_key_field)
                         .push(
-                            TextInput::new(
-                                &mut self.client_key_input,
-                                "super_secret_key",
-                                &self.client_key,
-                                Message::ClientKeyChanged,
+                            Button::new(&mut self.reveal_key_button, Text::new(reveal_key_label))
+                                .on_press(Message::ToggleClientKeyRevealed)
+                                .padding(Padding::from(8)),
+                        )
+                        .align_items(iced::Alignment::Center)
+                        .spacing(8),
+                )
+                .push(
+                    Row::new()
+                        .push(Text::new("Keep client key:"))
+                        .push(
+                            PickList::new(
+                                &mut self.client_key_storage_list,
+                                credentials::KeyStorage::AVAILABLE,
+                                Some(self.client_key_storage),
+                                Message::ClientKeyStorageChanged,
                             )
-                            .padding(Padding::from(8)),
+                            .padding(8),
                         )
                         .align_items(iced::Alignment::Center)
                         .spacing(8),
@@ -179,14 +332,67 @@ impl Application for SpicyGarden {
                         .spacing(8),
                 )
                 .push(
-                    Button::new(&mut self.start_button, Text::new("Start gathering data"))
-                        .on_press(Message::StartSeedSearch)
-                        .padding(Padding::from(16)),
+                    Row::new()
+                        .push(
+                            Button::new(&mut self.start_button, Text::new("Start gathering data"))
+                                .on_press(Message::StartSeedSearch)
+                                .padding(Padding::from(16)),
+                        )
+                        .push(
+                            Button::new(&mut self.check_environment_button, Text::new("Check environment"))
+                                .on_press(Message::CheckEnvironment)
+                                .padding(Padding::from(16)),
+                        )
+                        .push(
+                            Button::new(&mut self.test_connection_button, Text::new("Test connection"))
+                                .on_press(Message::TestConnection)
+                                .padding(Padding::from(16)),
+                        )
+                        .push(
+                            Button::new(&mut self.save_button, Text::new("Save settings"))
+                                .on_press(Message::SaveSettings)
+                                .padding(Padding::from(16)),
+                        )
+                        .spacing(8),
                 )
                 .push(Text::new(self.status_message.clone()))
+                .push(diagnostics::view_checklist(&self.diagnostics))
+                .push(Text::new(format!(
+                    "Lifetime: {}",
+                    self.lifetime_stats.client(&self.client_key).summary()
+                )))
         };
 
-        if self.running_state == RunningState::Running {
+        if let Some(update_info) = &self.available_update {
+            let mut update_row = Row::new()
+                .push(Text::new(format!(
+                    "SpicyGarden {} is available (this is {})",
+                    update_info.version,
+                    gatherserver::CLIENT_VERSION
+                )))
+                .align_items(iced::Alignment::Center)
+                .spacing(8);
+
+            if update_info.url.is_some() {
+                update_row = update_row.push(
+                    Button::new(&mut self.download_update_button, Text::new("Download update"))
+                        .on_press(Message::DownloadUpdate)
+                        .padding(Padding::from(8)),
+                );
+            };
+
+            column = column.push(update_row);
+
+            if let Some(notes) = &update_info.notes {
+                column = column.push(Text::new(notes.clone()).size(16));
+            };
+        };
+
+        if !self.update_status.is_empty() {
+            column = column.push(Text::new(self.update_status.clone()));
+        };
+
+        if self.running_state == RunningState::Running || self.running_state == RunningState::Paused {
             column = column
                 .push(Text::new(self.status_message.clone()))
                 .push(Text::new(format!("Seeds searched so far: {}", self.searched_seed_count)));
@@ -197,9 +403,56 @@ impl Application for SpicyGarden {
                 let seeds_per_minute: f32 = (self.searched_seed_count as f32) / (running_duration / 60.0);
 
                 column = column
-                    .push(Text::new(format!("Seeds per minute: {:.2}", seeds_per_minute)))
+                    .push(Text::new(format!("Seeds per minute (lifetime): {:.2}", seeds_per_minute)))
                     .push(Text::new(format!("Uptime: {:.2} hours", duration_hours)));
             };
+
+            column = column.push(Text::new(format!(
+                "Lifetime: {}",
+                self.lifetime_stats.client(&self.search_client_key).summary()
+            )));
+
+            let pause_button = if self.running_state == RunningState::Paused {
+                Button::new(&mut self.pause_button, Text::new("Resume"))
+                    .on_press(Message::ResumeSeedSearch)
+            } else {
+                Button::new(&mut self.pause_button, Text::new("Pause"))
+                    .on_press(Message::PauseSeedSearch)
+            };
+
+            column = column.push(
+                Row::new()
+                    .push(pause_button.padding(Padding::from(8)))
+                    .push(
+                        Button::new(&mut self.stop_button, Text::new("Stop"))
+                            .on_press(Message::StopSeedSearch)
+                            .padding(Padding::from(8)),
+                    )
+                    .spacing(8),
+            )
+            .push(runnerstatus::view_runner_table(&self.runner_statuses))
+            .push(runnerstatus::view_event_log(&self.recent_events))
+            .push(charts::view_charts(&self.search_history));
+
+            if !self.recent_finds.is_empty() {
+                column = column.push(rules::view_finds(&self.recent_finds));
+            };
+
+            if !self.recent_previews.is_empty() {
+                let found_seeds: Vec<&str> =
+                    self.recent_finds.iter().map(|find| find.seed.as_str()).collect();
+                column = column
+                    .push(Text::new("Recent seeds"))
+                    .push(preview::view_previews(&self.recent_previews, &found_seeds));
+            };
+        };
+
+        if self.running_state == RunningState::RunningPreflight {
+            column = column.push(Text::new(self.status_message.clone()))
+        };
+
+        if self.running_state == RunningState::Stopping {
+            column = column.push(Text::new("Stopping..."))
         };
 
         if self.running_state == RunningState::Quitting {
@@ -212,15 +465,16 @@ impl Application for SpicyGarden {
             .width(iced_native::Length::Fill)
             .align_items(iced::Alignment::Center);
 
-        Element::from(column)
+        Element::from(
+            Scrollable::new(&mut self.scroll)
+                .width(iced_native::Length::Fill)
+                .push(column),
+        )
     }
 
     fn update(&mut self, message: Self::Message) -> iced::Command<Self::Message> {
         match message {
             Message::StartSeedSearch => {
-                let server_address = self.server_address.clone();
-                let client_key = self.client_key.clone();
-
                 let runner_count = match self.runner_count.parse::<u32>() {
                     Ok(value) => value,
                     Err(_e) => {
@@ -229,21 +483,277 @@ impl Application for SpicyGarden {
                     }
                 };
 
-                self.status_message = format!("Collecting data with {} runners...", runner_count)
-                    .to_string();
-                self.running_state = RunningState::Running;
-                self.started_running_at = Some(Instant::now());
+                let errors = self.current_settings(runner_count).validate();
+                if !errors.is_empty() {
+                    self.status_message = errors
+                        .iter()
+                        .map(|error| format!("ERROR: {}", error))
+                        .collect::<Vec<String>>()
+                        .join("\n");
+                    return iced::Command::none();
+                };
+
+                // Remember the settings that started this search. A failed save shouldn't stop the search.
+                let save_error = match self.save_settings(runner_count) {
+                    Ok(()) => String::new(),
+                    Err(e) => format!("ERROR: Couldn't save settings: {}\n", e),
+                };
+
+                // Make sure this machine and the gather server are going to work out before any runners start.
+                // The connection comes first, since syncing the template needs it too.
+                self.running_state = RunningState::RunningPreflight;
+                self.status_message = format!("{}Checking connection to gather server...", save_error);
 
                 return iced::Command::perform(
-                    runner::seed_search_async_wrapper(server_address, client_key, runner_count),
-                    |_| Message::StoppedSeedSearch,
+                    gatherserver::check_connection_async_wrapper(
+                        self.server_address.clone(),
+                        self.client_key.clone(),
+                    ),
+                    move |result| Message::ConnectionChecked(runner_count, result),
                 );
             }
-            Message::StoppedSeedSearch => {
-                self.running_state = RunningState::Quit;
+            Message::ConnectionChecked(runner_count, result) => {
+                // The user may have closed the window while we were checking
+                if self.running_state != RunningState::RunningPreflight {
+                    return iced::Command::none();
+                };
+
+                if let Err(e) = result {
+                    self.running_state = RunningState::Waiting;
+                    self.status_message = format!("ERROR: {}", e);
+                    return iced::Command::none();
+                };
+
+                self.status_message = "Checking server template...".to_string();
+
+                return iced::Command::perform(
+                    servertemplate::sync_template_async_wrapper(
+                        self.server_address.clone(),
+                        self.client_key.clone(),
+                    ),
+                    move |result| Message::TemplateSynced(runner_count, result),
+                );
             }
-            Message::SearchedSeedCountUpdated(value) => {
-                self.searched_seed_count = value;
+            Message::TemplateSynced(runner_count, result) => {
+                // The user may have closed the window while we were downloading
+                if self.running_state != RunningState::RunningPreflight {
+                    return iced::Command::none();
+                };
+
+                match result {
+                    Ok(summary) => println!("{}", summary),
+                    Err(e) => {
+                        self.running_state = RunningState::Waiting;
+                        self.status_message = format!("ERROR: {}", e);
+                        return iced::Command::none();
+                    }
+                };
+
+                self.status_message = "Checking environment...".to_string();
+
+                return iced::Command::perform(
+                    diagnostics::run_diagnostics_async_wrapper(runner_count),
+                    move |checks| Message::DiagnosticsFinished(Some(runner_count), checks),
+                );
+            }
+            Message::CheckEnvironment => {
+                let runner_count = self.runner_count.parse::<u32>().unwrap_or(1);
+                self.status_message = "Checking environment...".to_string();
+
+                return iced::Command::perform(
+                    diagnostics::run_diagnostics_async_wrapper(runner_count),
+                    |checks| Message::DiagnosticsFinished(None, checks),
+                );
+            }
+            Message::DiagnosticsFinished(starting_runner_count, checks) => {
+                let failed = diagnostics::any_failed(&checks);
+                self.diagnostics = checks;
+
+                let runner_count = match starting_runner_count {
+                    // Just a check requested from the configuration screen
+                    None => {
+                        self.status_message = if failed {
+                            "ERROR: Some environment checks failed".to_string()
+                        } else {
+                            "Environment looks good".to_string()
+                        };
+                        return iced::Command::none();
+                    }
+                    Some(runner_count) => runner_count,
+                };
+
+                // The user may have closed the window while we were checking
+                if self.running_state != RunningState::RunningPreflight {
+                    return iced::Command::none();
+                };
+
+                if failed {
+                    self.running_state = RunningState::Waiting;
+                    self.status_message = "ERROR: Fix the failed environment checks before starting".to_string();
+                    return iced::Command::none();
+                };
+
+                self.status_message = "Handshaking with gather server...".to_string();
+
+                return iced::Command::perform(
+                    gatherserver::preflight_async_wrapper(
+                        self.server_address.clone(),
+                        self.client_key.clone(),
+                    ),
+                    move |result| Message::PreflightFinished(runner_count, result),
+                );
+            }
+            Message::PreflightFinished(runner_count, result) => {
+                // The user may have closed the window while we were checking
+                if self.running_state != RunningState::RunningPreflight {
+                    return iced::Command::none();
+                };
+
+                match result {
+                    Ok((handshake, templates)) => {
+                        self.begin_seed_search(runner_count, handshake, templates)
+                    }
+                    Err(e) => {
+                        self.running_state = RunningState::Waiting;
+                        self.status_message = format!("ERROR: {}", e);
+                    }
+                };
+            }
+            Message::TestConnection => {
+                self.status_message = "Testing connection to gather server...".to_string();
+
+                return iced::Command::perform(
+                    gatherserver::check_connection_async_wrapper(
+                        self.server_address.clone(),
+                        self.client_key.clone(),
+                    ),
+                    Message::ConnectionTested,
+                );
+            }
+            Message::ConnectionTested(result) => {
+                self.status_message = match result {
+                    Ok(()) => "Connection OK: gather server is reachable and accepted the client key"
+                        .to_string(),
+                    Err(e) => format!("ERROR: {}", e),
+                };
+            }
+            Message::SaveSettings => {
+                let runner_count = match self.runner_count.parse::<u32>() {
+                    Ok(value) => value,
+                    Err(_e) => {
+                        self.status_message = "ERROR: Invalid target runner count".to_string();
+                        return iced::Command::none();
+                    }
+                };
+
+                self.status_message = match self.save_settings(runner_count) {
+                    Ok(()) => format!("Settings saved to {}", self.config_path),
+                    Err(e) => format!("ERROR: {}", e),
+                };
+            }
+            Message::UpdateChecked(result) => {
+                match result {
+                    Ok(available_update) => self.available_update = available_update,
+                    // Not being able to check for updates isn't worth bothering anyone about
+                    Err(e) => println!("ERROR: {}", e),
+                };
+            }
+            Message::DownloadUpdate => {
+                if let Some(update_info) = &self.available_update {
+                    self.update_status = format!("Downloading SpicyGarden {}...", update_info.version);
+
+                    return iced::Command::perform(
+                        update::download_update_async_wrapper(update_info.clone()),
+                        Message::UpdateDownloaded,
+                    );
+                };
+            }
+            Message::UpdateDownloaded(result) => {
+                self.update_status = match result {
+                    Ok(summary) => summary,
+                    Err(e) => format!("ERROR: {}", e),
+                };
+            }
+            Message::PauseSeedSearch => {
+                if self.running_state == RunningState::Running {
+                    if let Some(handle) = &self.runner {
+                        handle.pause();
+                    };
+                    self.account_runtime();
+                    self.running_state = RunningState::Paused;
+                    self.status_message =
+                        "Paused. Runners will finish their current seeds.".to_string();
+                };
+            }
+            Message::ResumeSeedSearch => {
+                if self.running_state == RunningState::Paused {
+                    if let Some(handle) = &self.runner {
+                        handle.resume();
+                    };
+                    self.account_runtime();
+                    self.running_state = RunningState::Running;
+                    self.status_message = format!("Collecting data with {} runners...", self.runner_count);
+                };
+            }
+            Message::StopSeedSearch => {
+                if self.running_state == RunningState::Running || self.running_state == RunningState::Paused {
+                    if let Some(handle) = &self.runner {
+                        handle.stop();
+                    };
+                    self.account_runtime();
+                    self.running_state = RunningState::Stopping;
+                };
+            }
+            Message::RunnerEventReceived(search_id, event) => {
+                // Ignore stragglers from a search we've already moved on from
+                match &self.runner {
+                    Some(handle) if handle.id() == search_id => (),
+                    _ => return iced::Command::none(),
+                };
+
+                runnerstatus::update_runner_statuses(&mut self.runner_statuses, &event);
+                if let Some(description) = runnerstatus::describe_event(&event) {
+                    self.recent_events.insert(0, description);
+                    self.recent_events.truncate(runnerstatus::RECENT_EVENT_COUNT);
+                };
+                self.search_history.record_event(&event);
+
+                self.account_runtime();
+                let stats_changed = self.lifetime_stats.record_event(&self.search_client_key, &event);
+
+                match event {
+                    runner::RunnerEvent::SubmissionSucceeded { .. } => {
+                        self.searched_seed_count += 1;
+                    }
+                    runner::RunnerEvent::PreviewRendered { preview, .. } => {
+                        self.recent_previews.insert(0, preview);
+                        self.recent_previews.truncate(preview::RECENT_PREVIEW_COUNT);
+                    }
+                    runner::RunnerEvent::RuleMatched { seed, rule, reasons } => {
+                        self.recent_finds.insert(0, rules::Find { seed, rule, reasons });
+                        self.recent_finds.truncate(rules::RECENT_FIND_COUNT);
+                    }
+                    runner::RunnerEvent::SearchStopped => {
+                        self.runner = None;
+                        self.runtime_accounted_at = None;
+                        self.lifetime_stats.save();
+
+                        if self.running_state == RunningState::Quitting {
+                            self.running_state = RunningState::Quit;
+                        } else {
+                            self.running_state = RunningState::Waiting;
+                            self.status_message = format!(
+                                "Stopped after searching {} seeds.",
+                                self.searched_seed_count
+                            );
+                        };
+                    }
+                    _ => (),
+                };
+
+                if stats_changed {
+                    self.lifetime_stats.save();
+                };
             }
             Message::ServerAddressChanged(value) => {
                 self.server_address = value;
@@ -251,17 +761,28 @@ impl Application for SpicyGarden {
             Message::ClientKeyChanged(value) => {
                 self.client_key = value;
             }
+            Message::ToggleClientKeyRevealed => {
+                self.client_key_revealed = !self.client_key_revealed;
+            }
+            Message::ClientKeyStorageChanged(value) => {
+                self.client_key_storage = value;
+            }
             Message::RunnerCountChanged(value) => {
                 self.runner_count = value;
             }
-            Message::Quit => {
-                if self.running_state == RunningState::Running {
+            Message::Quit => match self.running_state {
+                RunningState::Running | RunningState::Paused | RunningState::Stopping => {
+                    self.account_runtime();
                     self.running_state = RunningState::Quitting;
-                    runner::JAVA_THREADS_SHUTDOWN.store(true, atomic::Ordering::Relaxed);
-                } else {
+                    if let Some(handle) = &self.runner {
+                        handle.stop();
+                    };
+                }
+                RunningState::Quitting => (),
+                _ => {
                     self.running_state = RunningState::Quit;
-                };
-            }
+                }
+            },
             Message::IgnorableEvent => {}
         }
         iced::Command::none()
@@ -272,40 +793,153 @@ impl Application for SpicyGarden {
     }
 }
 
+impl SpicyGarden {
+    fn begin_seed_search(
+        &mut self,
+        runner_count: u32,
+        handshake: gatherserver::Handshake,
+        templates: Vec<servertemplate::ReadyTemplate>,
+    ) {
+        let server_address = self.server_address.clone();
+        let client_key = self.client_key.clone();
+
+        self.status_message = format!("Collecting data with {} runners...", runner_count)
+            .to_string();
+        self.running_state = RunningState::Running;
+        self.started_running_at = Some(Instant::now());
+
+        self.searched_seed_count = 0;
+        self.runner_statuses = runnerstatus::new_runner_statuses(runner_count);
+        self.search_history = history::SearchHistory::default();
+        self.recent_previews.clear();
+        self.recent_finds.clear();
+        self.recent_events.clear();
+
+        self.search_client_key = client_key.clone();
+        self.runtime_accounted_at = Some(Instant::now());
+
+        self.runner = Some(runner::start_seed_search(
+            runner::SearchSettings {
+                server_address,
+                client_key,
+                runner_count,
+                store_results: self.store_results,
+                rules: self.rules.clone(),
+            },
+            handshake,
+            templates,
+        ));
+    }
+
+    fn current_settings(&self, runner_count: u32) -> config::Config {
+        config::Config {
+            server_address: self.server_address.clone(),
+            client_key: self.client_key.clone(),
+            client_key_storage: self.client_key_storage,
+            runner_count,
+            store_results: self.store_results,
+            rules: self.rules.clone(),
+        }
+    }
+
+    // Overrides from the command line or environment only get saved if they've been edited
+    fn save_settings(&mut self, runner_count: u32) -> Result<(), String> {
+        let settings = self
+            .overrides
+            .keep_out_of(self.current_settings(runner_count), &self.file_config);
+        config::save_config(&self.config_path, &settings)?;
+        self.file_config = settings;
+        Ok(())
+    }
+
+    // Adds time spent running since we last checked to the lifetime stats.
+    // Time spent paused doesn't count.
+    fn account_runtime(&mut self) {
+        if let Some(accounted_at) = self.runtime_accounted_at {
+            if self.running_state == RunningState::Running {
+                self.lifetime_stats
+                    .add_runtime(&self.search_client_key, accounted_at.elapsed());
+            };
+            self.runtime_accounted_at = Some(Instant::now());
+        };
+    }
+}
+
 fn main() {
+    // If an update was downloaded last time, run that instead
+    if let Some(exit_code) = update::apply_pending_update() {
+        std::process::exit(exit_code);
+    };
+
     println!("SpicyGarden by wilm0x42 commit {}", env!("GIT_HASH"));
     
-    // Define default initial values
+    // Pull out config options, if we've been given any
+
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
 
-    let mut flags = SpicyGardenFlags {
-        server_address: "".to_string(),
-        client_key: "".to_string(),
-        runner_count: 1,
+    let config_path = match config::take_option(&mut args, "--config") {
+        Ok(config_path) => config_path.unwrap_or_else(|| config::DEFAULT_CONFIG_PATH.to_string()),
+        Err(e) => {
+            println!("ERROR: {}", e);
+            return;
+        }
     };
 
-    // Load values from config.toml, if possible
+    let overrides = match config::ConfigOverrides::from_env_and_args(&mut args) {
+        Ok(overrides) => overrides,
+        Err(e) => {
+            println!("ERROR: {}", e);
+            return;
+        }
+    };
 
-    match fs::read("config.toml") {
-        Ok(toml_slice) => {
-            match toml::from_slice::<SpicyGardenFlags>(&toml_slice) {
-                Ok(config) => {
-                    flags = config;
-                },
-                Err(e) => {
-                    println!("ERROR: Failed to parse config.toml: {:?}", e);
-                }
-            }
-        },
-        Err(_) => {
-            println!("Couldn't read config.toml, using default values.");
+    // Load values from the config file, if possible
+
+    let (config, file_config, config_errors) = config::load_config(&config_path, &overrides);
+
+    let flags = SpicyGardenFlags {
+        config,
+        file_config,
+        overrides,
+        config_path,
+        config_errors,
+    };
+
+    // Handle command line subcommands instead of starting the GUI, if we've been given one
+
+    match args.first().map(|arg| arg.as_str()) {
+        Some("stats") => {
+            let reconcile = args.iter().any(|arg| arg == "--reconcile");
+            stats::print_stats(&flags.config.server_address, &flags.config.client_key, reconcile);
+            return;
+        }
+        Some("query") => {
+            resultsdb::run_query_command(&args[1..]);
+            return;
+        }
+        Some("export") => {
+            export::run_export_command(&args[1..]);
+            return;
         }
+        Some(command) => {
+            println!("Unknown command: {}", command);
+            println!(
+                "Usage: SpicyGarden [--config <path>] [--server-address <address>] \
+                 [--client-key <key>] [--runner-count <count>] \
+                 [stats [--reconcile] | query <conditions> | export <options>]"
+            );
+            return;
+        }
+        None => (),
     };
 
     // Start the GUI
 
-    let mut settings: Settings<SpicyGardenFlags> = Settings::default();
-    settings.flags = flags;
-    settings.window.size = (400, 300);
-    settings.exit_on_close_request = false;
+    let mut settings: Settings<SpicyGardenFlags> = Settings {
+        flags,
+        exit_on_close_request: false,
+        ..Settings::default()
+    };
+    settings.window.size = (800, 600);
     SpicyGarden::run(settings).unwrap();
 }
@@ -0,0 +1,305 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::Read;
+
+use flate2::read::GzDecoder;
+
+// Compounds nested deeper than this are almost certainly a corrupt file
+const MAX_DEPTH: u32 = 512;
+
+// One value from an NBT file, the format Minecraft saves nearly everything in
+#[derive(Debug, Clone, PartialEq)]
+pub enum Tag {
+    Byte(i8),
+    Short(i16),
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    ByteArray(Vec<i8>),
+    String(String),
+    List(Vec<Tag>),
+    Compound(HashMap<String, Tag>),
+    IntArray(Vec<i32>),
+    LongArray(Vec<i64>),
+}
+
+impl Tag {
+    pub fn get(&self, key: &str) -> Option<&Tag> {
+        match self {
+            Tag::Compound(entries) => entries.get(key),
+            _ => None,
+        }
+    }
+
+    // Follows a chain of compound keys, e.g. ["Data", "WorldGenSettings", "seed"]
+    pub fn path(&self, keys: &[&str]) -> Option<&Tag> {
+        keys.iter().try_fold(self, |tag, key| tag.get(key))
+    }
+
+    // Any of the integer types, widened
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            Tag::Byte(value) => Some(*value as i64),
+            Tag::Short(value) => Some(*value as i64),
+            Tag::Int(value) => Some(*value as i64),
+            Tag::Long(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Tag::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_list(&self) -> Option<&[Tag]> {
+        match self {
+            Tag::List(values) => Some(values),
+            _ => None,
+        }
+    }
+
+    pub fn as_long_array(&self) -> Option<&[i64]> {
+        match self {
+            Tag::LongArray(values) => Some(values),
+            _ => None,
+        }
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, count: usize) -> Result<&'a [u8], String> {
+        if self.data.len() - self.position < count {
+            return Err("NBT data ends unexpectedly".to_string());
+        }
+
+        let taken = &self.data[self.position..self.position + count];
+        self.position += count;
+        Ok(taken)
+    }
+
+    fn read_u8(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn read_i16(&mut self) -> Result<i16, String> {
+        let bytes = self.take(2)?;
+        Ok(i16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn read_i32(&mut self) -> Result<i32, String> {
+        let bytes = self.take(4)?;
+        Ok(i32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn read_i64(&mut self) -> Result<i64, String> {
+        let mut bytes = [0u8; 8];
+        bytes.copy_from_slice(self.take(8)?);
+        Ok(i64::from_be_bytes(bytes))
+    }
+
+    // Every element takes at least element_size bytes, so a length the rest of the data
+    // can't possibly hold is caught here rather than after a long loop
+    fn read_length(&mut self, element_size: usize) -> Result<usize, String> {
+        let length = self.read_i32()?;
+        if length < 0 {
+            return Err(format!("NBT has a negative length of {}", length));
+        }
+
+        let remaining = self.data.len() - self.position;
+        if (length as usize).saturating_mul(element_size) > remaining {
+            return Err(format!(
+                "NBT has a length of {} with only {} bytes left",
+                length, remaining
+            ));
+        }
+        Ok(length as usize)
+    }
+
+    // Java writes "modified UTF-8", which only differs from the real thing for
+    // null characters and emoji, neither of which matter to us
+    fn read_string(&mut self) -> Result<String, String> {
+        let length = self.read_i16()? as u16 as usize;
+        Ok(String::from_utf8_lossy(self.take(length)?).to_string())
+    }
+
+    fn read_payload(&mut self, tag_type: u8, depth: u32) -> Result<Tag, String> {
+        if depth > MAX_DEPTH {
+            return Err("NBT is nested too deeply".to_string());
+        }
+
+        let tag = match tag_type {
+            1 => Tag::Byte(self.read_u8()? as i8),
+            2 => Tag::Short(self.read_i16()?),
+            3 => Tag::Int(self.read_i32()?),
+            4 => Tag::Long(self.read_i64()?),
+            5 => Tag::Float(f32::from_bits(self.read_i32()? as u32)),
+            6 => Tag::Double(f64::from_bits(self.read_i64()? as u64)),
+            7 => {
+                let length = self.read_length(1)?;
+                Tag::ByteArray(self.take(length)?.iter().map(|byte| *byte as i8).collect())
+            }
+            8 => Tag::String(self.read_string()?),
+            9 => {
+                let element_type = self.read_u8()?;
+                let length = self.read_length(1)?;
+                let mut values = Vec::with_capacity(length.min(4096));
+                for _ in 0..length {
+                    values.push(self.read_payload(element_type, depth + 1)?);
+                }
+                Tag::List(values)
+            }
+            10 => {
+                let mut entries = HashMap::new();
+                loop {
+                    let entry_type = self.read_u8()?;
+                    if entry_type == 0 {
+                        break;
+                    }
+                    let name = self.read_string()?;
+                    entries.insert(name, self.read_payload(entry_type, depth + 1)?);
+                }
+                Tag::Compound(entries)
+            }
+            11 => {
+                let length = self.read_length(4)?;
+                let mut values = Vec::with_capacity(length.min(4096));
+                for _ in 0..length {
+                    values.push(self.read_i32()?);
+                }
+                Tag::IntArray(values)
+            }
+            12 => {
+                let length = self.read_length(8)?;
+                let mut values = Vec::with_capacity(length.min(4096));
+                for _ in 0..length {
+                    values.push(self.read_i64()?);
+                }
+                Tag::LongArray(values)
+            }
+            tag_type => return Err(format!("Unknown NBT tag type {}", tag_type)),
+        };
+
+        Ok(tag)
+    }
+}
+
+// Reads uncompressed NBT, returning the root tag (always a compound in practice)
+pub fn read(data: &[u8]) -> Result<Tag, String> {
+    let mut reader = Reader { data, position: 0 };
+
+    let root_type = reader.read_u8()?;
+    let _root_name = reader.read_string()?;
+    reader.read_payload(root_type, 0)
+}
+
+// level.dat and friends are gzipped NBT
+pub fn read_gzip_file(path: &str) -> Result<Tag, String> {
+    let compressed = fs::read(path).map_err(|e| format!("Unable to read {}: {}", path, e))?;
+
+    let mut data = vec![];
+    GzDecoder::new(&compressed[..])
+        .read_to_end(&mut data)
+        .map_err(|e| format!("Unable to decompress {}: {}", path, e))?;
+
+    read(&data).map_err(|e| format!("Unable to parse {}: {}", path, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A root compound named "" holding the given entries, each of which is already encoded
+    fn compound(entries: &[Vec<u8>]) -> Vec<u8> {
+        let mut data = vec![10, 0, 0];
+        for entry in entries {
+            data.extend_from_slice(entry);
+        }
+        data.push(0);
+        data
+    }
+
+    fn entry(tag_type: u8, name: &str, payload: &[u8]) -> Vec<u8> {
+        let mut data = vec![tag_type];
+        data.extend_from_slice(&(name.len() as u16).to_be_bytes());
+        data.extend_from_slice(name.as_bytes());
+        data.extend_from_slice(payload);
+        data
+    }
+
+    #[test]
+    fn small_compounds_are_read() {
+        let mut list = vec![3];
+        list.extend_from_slice(&2i32.to_be_bytes());
+        list.extend_from_slice(&7i32.to_be_bytes());
+        list.extend_from_slice(&(-7i32).to_be_bytes());
+
+        let mut name = 5u16.to_be_bytes().to_vec();
+        name.extend_from_slice(b"world");
+
+        let mut longs = 1i32.to_be_bytes().to_vec();
+        longs.extend_from_slice(&i64::MIN.to_be_bytes());
+
+        let data = compound(&[
+            entry(1, "flag", &[1]),
+            entry(3, "SpawnX", &(-120i32).to_be_bytes()),
+            entry(4, "seed", &42i64.to_be_bytes()),
+            entry(8, "LevelName", &name),
+            entry(9, "values", &list),
+            entry(12, "longs", &longs),
+        ]);
+
+        let root = read(&data).unwrap();
+        assert_eq!(root.get("flag").and_then(Tag::as_i64), Some(1));
+        assert_eq!(root.get("SpawnX").and_then(Tag::as_i64), Some(-120));
+        assert_eq!(root.get("seed").and_then(Tag::as_i64), Some(42));
+        assert_eq!(root.get("LevelName").and_then(Tag::as_str), Some("world"));
+        assert_eq!(
+            root.get("values").and_then(Tag::as_list),
+            Some(&[Tag::Int(7), Tag::Int(-7)][..])
+        );
+        assert_eq!(root.get("longs").and_then(Tag::as_long_array), Some(&[i64::MIN][..]));
+        assert_eq!(root.get("missing"), None);
+    }
+
+    #[test]
+    fn truncated_data_is_an_error() {
+        let data = compound(&[
+            entry(3, "SpawnX", &(-120i32).to_be_bytes()),
+            entry(4, "seed", &42i64.to_be_bytes()),
+        ]);
+        assert!(read(&data).is_ok());
+
+        for length in 0..data.len() {
+            assert!(read(&data[..length]).is_err(), "{} bytes were accepted", length);
+        }
+    }
+
+    #[test]
+    fn oversized_lengths_are_an_error() {
+        let mut list = vec![1];
+        list.extend_from_slice(&i32::MAX.to_be_bytes());
+        assert!(read(&compound(&[entry(9, "list", &list)])).is_err());
+
+        for array_type in &[7, 11, 12] {
+            let array = i32::MAX.to_be_bytes();
+            assert!(read(&compound(&[entry(*array_type, "array", &array)])).is_err());
+        }
+
+        // Two longs' worth of bytes isn't enough for three
+        let mut longs = 3i32.to_be_bytes().to_vec();
+        longs.extend_from_slice(&[0; 16]);
+        assert!(read(&compound(&[entry(12, "longs", &longs)])).is_err());
+
+        let negative = (-1i32).to_be_bytes();
+        assert!(read(&compound(&[entry(11, "ints", &negative)])).is_err());
+    }
+}
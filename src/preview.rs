@@ -0,0 +1,452 @@
+use std::fs;
+
+use iced::canvas::{self, Canvas, Cursor, Frame, Geometry};
+use iced::{Color, Column, Length, Point, Rectangle, Row, Size, Text};
+use sha2::{Digest, Sha256};
+
+use crate::rules;
+use crate::seedresult::ParsedResult;
+use crate::Message;
+
+// Every rendered preview is saved in here as <seed>.png
+pub const PREVIEW_DIR: &str = "previews";
+
+// How many of the latest seeds the GUI keeps previews of
+pub const RECENT_PREVIEW_COUNT: usize = 4;
+
+// Pixels along each side of the saved image
+const IMAGE_SIZE: usize = 256;
+
+// The GUI draws the preview as rectangles rather than pixels, so it gets a coarser grid
+const GRID_SIZE: usize = 64;
+const PREVIEW_CANVAS_SIZE: u16 = 192;
+
+// Samples are bucketed into this many squares along each side of the map, so that finding
+// the nearest one for each pixel only has to look at the few buckets around it
+const SAMPLE_BUCKET_COUNT: usize = 32;
+
+// Half the width of the square marking a structure, and of the cross marking spawn
+const STRUCTURE_MARKER_RADIUS: i64 = 3;
+const SPAWN_MARKER_RADIUS: i64 = 5;
+
+const STRUCTURE_COLOR: [u8; 3] = [220, 30, 30];
+const SPAWN_COLOR: [u8; 3] = [255, 255, 255];
+const OUTLINE_COLOR: [u8; 3] = [0, 0, 0];
+
+// A seed's biome map, small enough to hand to the GUI
+#[derive(Debug, Clone)]
+pub struct BiomePreview {
+    pub seed: String,
+    // GRID_SIZE rows of GRID_SIZE cells, north at the top
+    grid: Vec<[u8; 3]>,
+    // Marker positions as fractions of the way across and down the map
+    structures: Vec<(f32, f32)>,
+    spawn: Option<(f32, f32)>,
+}
+
+fn blend(color: [u8; 3], other: [u8; 3], amount: f32) -> [u8; 3] {
+    let mix = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * amount) as u8;
+    [mix(color[0], other[0]), mix(color[1], other[1]), mix(color[2], other[2])]
+}
+
+// Roughly the colors the game's own maps use. Biome names vary a little between
+// versions, so they're matched by what they have in common rather than exactly.
+fn biome_color(biome: &str) -> [u8; 3] {
+    let biome = biome.to_uppercase();
+    let has = |word: &str| biome.contains(word);
+
+    if has("OCEAN") {
+        let ocean = if has("WARM") {
+            [40, 120, 200]
+        } else if has("COLD") || has("FROZEN") {
+            [40, 60, 150]
+        } else {
+            [30, 80, 180]
+        };
+        if has("DEEP") {
+            blend(ocean, [0, 0, 40], 0.4)
+        } else {
+            ocean
+        }
+    } else if has("RIVER") {
+        if has("FROZEN") {
+            [160, 160, 255]
+        } else {
+            [60, 110, 230]
+        }
+    } else if has("BEACH") || has("SHORE") {
+        if has("STONY") {
+            [150, 150, 140]
+        } else {
+            [250, 222, 140]
+        }
+    } else if has("MUSHROOM") {
+        [200, 80, 220]
+    } else if has("ICE") || has("FROZEN") || has("SNOWY") {
+        [235, 245, 250]
+    } else if has("PEAKS") || has("SLOPES") || has("WINDSWEPT") || has("MOUNTAIN") || has("HILLS") {
+        [130, 130, 130]
+    } else if has("DESERT") {
+        [250, 200, 90]
+    } else if has("BADLANDS") || has("MESA") {
+        [215, 100, 40]
+    } else if has("SAVANNA") {
+        [190, 180, 90]
+    } else if has("JUNGLE") {
+        [40, 170, 20]
+    } else if has("SWAMP") {
+        [70, 90, 50]
+    } else if has("DARK_FOREST") || has("ROOFED") {
+        [40, 80, 20]
+    } else if has("CHERRY") {
+        [240, 170, 200]
+    } else if has("TAIGA") || has("GROVE") {
+        [50, 100, 80]
+    } else if has("FOREST") {
+        [60, 130, 40]
+    } else if has("PLAINS") || has("MEADOW") {
+        [140, 190, 90]
+    } else {
+        // Something new, so make one up that'll at least be the same every time
+        let hash = Sha256::digest(biome.as_bytes());
+        [hash[0], hash[1], hash[2]]
+    }
+}
+
+fn put_pixel(pixels: &mut [u8], x: i64, y: i64, color: [u8; 3]) {
+    if x < 0 || y < 0 || x >= IMAGE_SIZE as i64 || y >= IMAGE_SIZE as i64 {
+        return;
+    }
+    let offset = (y as usize * IMAGE_SIZE + x as usize) * 3;
+    pixels[offset..offset + 3].copy_from_slice(&color);
+}
+
+fn fill_square(pixels: &mut [u8], (x, y): (i64, i64), radius: i64, color: [u8; 3]) {
+    for dy in -radius..=radius {
+        for dx in -radius..=radius {
+            put_pixel(pixels, x + dx, y + dy, color);
+        }
+    }
+}
+
+fn draw_cross(pixels: &mut [u8], (x, y): (i64, i64), radius: i64, thickness: i64, color: [u8; 3]) {
+    for along in -radius..=radius {
+        for across in -thickness..=thickness {
+            put_pixel(pixels, x + along, y + across, color);
+            put_pixel(pixels, x + across, y + along, color);
+        }
+    }
+}
+
+fn encode_png(pixels: &[u8]) -> Result<Vec<u8>, String> {
+    let mut png_bytes = vec![];
+
+    let mut encoder = png::Encoder::new(&mut png_bytes, IMAGE_SIZE as u32, IMAGE_SIZE as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    encoder
+        .write_header()
+        .and_then(|mut writer| writer.write_image_data(pixels))
+        .map_err(|e| format!("Unable to encode preview: {}", e))?;
+
+    Ok(png_bytes)
+}
+
+// Biome samples sorted into a grid of buckets covering -extent..extent on both axes
+struct SampleBuckets<'a> {
+    parsed: &'a ParsedResult,
+    extent: f64,
+    bucket_size: f64,
+    buckets: Vec<Vec<usize>>,
+}
+
+impl<'a> SampleBuckets<'a> {
+    fn new(parsed: &'a ParsedResult, extent: f64) -> SampleBuckets<'a> {
+        let mut sample_buckets = SampleBuckets {
+            parsed,
+            extent,
+            bucket_size: 2.0 * extent / SAMPLE_BUCKET_COUNT as f64,
+            buckets: vec![vec![]; SAMPLE_BUCKET_COUNT * SAMPLE_BUCKET_COUNT],
+        };
+
+        for (index, sample) in parsed.biomes.iter().enumerate() {
+            let (row, column) = sample_buckets.bucket_of(sample.x as f64, sample.z as f64);
+            sample_buckets.buckets[row * SAMPLE_BUCKET_COUNT + column].push(index);
+        }
+
+        sample_buckets
+    }
+
+    fn bucket_of(&self, x: f64, z: f64) -> (usize, usize) {
+        let to_bucket = |coordinate: f64| {
+            (((coordinate + self.extent) / self.bucket_size).max(0.0) as usize).min(SAMPLE_BUCKET_COUNT - 1)
+        };
+        (to_bucket(z), to_bucket(x))
+    }
+
+    // Searches outwards a ring of buckets at a time. Anything in the next ring out is
+    // at least `ring * bucket_size` away, so once the best so far is closer than that, it's the one.
+    fn nearest(&self, x: f64, z: f64) -> usize {
+        let (row, column) = self.bucket_of(x, z);
+        let mut nearest: Option<(usize, f64)> = None;
+
+        for ring in 0..SAMPLE_BUCKET_COUNT {
+            let ring_rows = row.saturating_sub(ring)..=(row + ring).min(SAMPLE_BUCKET_COUNT - 1);
+
+            for ring_row in ring_rows {
+                let ring_columns = column.saturating_sub(ring)..=(column + ring).min(SAMPLE_BUCKET_COUNT - 1);
+
+                for ring_column in ring_columns {
+                    // Only the edge of the ring, since the inside was searched already
+                    if ring_row.abs_diff(row) != ring && ring_column.abs_diff(column) != ring {
+                        continue;
+                    }
+
+                    for &index in &self.buckets[ring_row * SAMPLE_BUCKET_COUNT + ring_column] {
+                        let sample = &self.parsed.biomes[index];
+                        let dx = sample.x as f64 - x;
+                        let dz = sample.z as f64 - z;
+                        let distance = dx * dx + dz * dz;
+
+                        let is_nearer = match nearest {
+                            Some((_, nearest_distance)) => distance < nearest_distance,
+                            None => true,
+                        };
+                        if is_nearer {
+                            nearest = Some((index, distance));
+                        }
+                    }
+                }
+            }
+
+            if let Some((index, distance)) = nearest {
+                let cleared = ring as f64 * self.bucket_size;
+                if distance <= cleared * cleared {
+                    return index;
+                }
+            }
+        }
+
+        // Every bucket has been searched by now, and there's always at least one sample
+        nearest.map(|(index, _)| index).unwrap_or(0)
+    }
+}
+
+// Colors every pixel after the nearest biome sample, which fills the gaps between the plugin's
+// spiral of samples with a Voronoi diagram. The map is centred on 0,0, where the plugin samples
+// from, and is just big enough to take in every sample.
+// Returns the preview for the GUI along with the PNG to save.
+pub fn render_preview(
+    seed: &str,
+    parsed: &ParsedResult,
+    spawn: Option<(i64, i64, i64)>,
+) -> Result<(BiomePreview, Vec<u8>), String> {
+    if parsed.biomes.is_empty() {
+        return Err("No biome samples to draw a preview from".to_string());
+    }
+
+    let extent = parsed
+        .biomes
+        .iter()
+        .map(|sample| sample.x.abs().max(sample.z.abs()))
+        .max()
+        .unwrap_or(0)
+        .max(16) as f64;
+
+    let sample_colors: Vec<[u8; 3]> = parsed
+        .biomes
+        .iter()
+        .map(|sample| biome_color(&sample.biome))
+        .collect();
+
+    // Fractions of the way across the map, which may fall outside it
+    let to_map = |x: i64, z: i64| {
+        (
+            ((x as f64 + extent) / (2.0 * extent)) as f32,
+            ((z as f64 + extent) / (2.0 * extent)) as f32,
+        )
+    };
+    let on_map = |(u, v): &(f32, f32)| (0.0..=1.0).contains(u) && (0.0..=1.0).contains(v);
+
+    let sample_buckets = SampleBuckets::new(parsed, extent);
+
+    let mut pixels = vec![0u8; IMAGE_SIZE * IMAGE_SIZE * 3];
+
+    for row in 0..IMAGE_SIZE {
+        let z = ((row as f64 + 0.5) / IMAGE_SIZE as f64) * 2.0 * extent - extent;
+
+        for column in 0..IMAGE_SIZE {
+            let x = ((column as f64 + 0.5) / IMAGE_SIZE as f64) * 2.0 * extent - extent;
+
+            let nearest = sample_buckets.nearest(x, z);
+
+            let offset = (row * IMAGE_SIZE + column) * 3;
+            pixels[offset..offset + 3].copy_from_slice(&sample_colors[nearest]);
+        }
+    }
+
+    // The GUI's grid is picked out of the image before any markers are drawn on it
+    let cell_size = IMAGE_SIZE / GRID_SIZE;
+    let grid = (0..GRID_SIZE * GRID_SIZE)
+        .map(|cell| {
+            let row = (cell / GRID_SIZE) * cell_size + cell_size / 2;
+            let column = (cell % GRID_SIZE) * cell_size + cell_size / 2;
+            let offset = (row * IMAGE_SIZE + column) * 3;
+            [pixels[offset], pixels[offset + 1], pixels[offset + 2]]
+        })
+        .collect();
+
+    let structures: Vec<(f32, f32)> = parsed
+        .structures
+        .iter()
+        .filter_map(|structure| structure.position)
+        .map(|(x, _, z)| to_map(x, z))
+        .filter(on_map)
+        .collect();
+    let spawn = spawn.map(|(x, _, z)| to_map(x, z)).filter(on_map);
+
+    let to_pixel = |(u, v): (f32, f32)| {
+        (
+            (u * IMAGE_SIZE as f32) as i64,
+            (v * IMAGE_SIZE as f32) as i64,
+        )
+    };
+
+    for structure in &structures {
+        let position = to_pixel(*structure);
+        fill_square(&mut pixels, position, STRUCTURE_MARKER_RADIUS + 1, OUTLINE_COLOR);
+        fill_square(&mut pixels, position, STRUCTURE_MARKER_RADIUS, STRUCTURE_COLOR);
+    }
+
+    if let Some(spawn) = spawn {
+        let position = to_pixel(spawn);
+        draw_cross(&mut pixels, position, SPAWN_MARKER_RADIUS + 1, 2, OUTLINE_COLOR);
+        draw_cross(&mut pixels, position, SPAWN_MARKER_RADIUS, 1, SPAWN_COLOR);
+    }
+
+    let png_bytes = encode_png(&pixels)?;
+
+    let preview = BiomePreview {
+        seed: seed.to_string(),
+        grid,
+        structures,
+        spawn,
+    };
+
+    Ok((preview, png_bytes))
+}
+
+// Seeds can be any text at all, so anything that isn't safe in a file name is swapped out,
+// with a bit of the seed's hash tacked on so that different seeds don't end up sharing a file
+fn preview_file_name(seed: &str) -> String {
+    let safe: String = seed
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' { c } else { '_' })
+        .collect();
+
+    if safe == seed && !safe.is_empty() {
+        format!("{}.png", safe)
+    } else {
+        let hash = format!("{:x}", Sha256::digest(seed.as_bytes()));
+        format!("{}_{}.png", safe, &hash[..8])
+    }
+}
+
+// Returns where the preview was saved
+pub fn save_preview(seed: &str, png_bytes: &[u8]) -> Result<String, String> {
+    fs::create_dir_all(PREVIEW_DIR)
+        .map_err(|e| format!("Unable to create {}: {}", PREVIEW_DIR, e))?;
+
+    let path = format!("{}/{}", PREVIEW_DIR, preview_file_name(seed));
+    fs::write(&path, png_bytes).map_err(|e| format!("Unable to write {}: {}", path, e))?;
+
+    Ok(path)
+}
+
+fn to_color(color: [u8; 3]) -> Color {
+    Color::from_rgb8(color[0], color[1], color[2])
+}
+
+impl canvas::Program<Message> for BiomePreview {
+    fn draw(&self, bounds: Rectangle, _cursor: Cursor) -> Vec<Geometry> {
+        let mut frame = Frame::new(bounds.size());
+
+        let cell_width = frame.width() / GRID_SIZE as f32;
+        let cell_height = frame.height() / GRID_SIZE as f32;
+
+        // Neighbouring cells are usually the same biome, so each run of them is one rectangle
+        for row in 0..GRID_SIZE {
+            let mut run_start = 0;
+            for column in 1..=GRID_SIZE {
+                let run_color = self.grid[row * GRID_SIZE + run_start];
+                if column < GRID_SIZE && self.grid[row * GRID_SIZE + column] == run_color {
+                    continue;
+                }
+
+                frame.fill_rectangle(
+                    Point::new(run_start as f32 * cell_width, row as f32 * cell_height),
+                    Size::new((column - run_start) as f32 * cell_width, cell_height),
+                    to_color(run_color),
+                );
+                run_start = column;
+            }
+        }
+
+        let (frame_width, frame_height) = (frame.width(), frame.height());
+        let to_point = |(u, v): (f32, f32)| Point::new(u * frame_width, v * frame_height);
+
+        for structure in &self.structures {
+            let center = to_point(*structure);
+            frame.fill_rectangle(
+                Point::new(center.x - 3.0, center.y - 3.0),
+                Size::new(6.0, 6.0),
+                to_color(OUTLINE_COLOR),
+            );
+            frame.fill_rectangle(
+                Point::new(center.x - 2.0, center.y - 2.0),
+                Size::new(4.0, 4.0),
+                to_color(STRUCTURE_COLOR),
+            );
+        }
+
+        if let Some(spawn) = self.spawn {
+            let center = to_point(spawn);
+            for (width, height, color) in [
+                (12.0, 4.0, OUTLINE_COLOR),
+                (4.0, 12.0, OUTLINE_COLOR),
+                (10.0, 2.0, SPAWN_COLOR),
+                (2.0, 10.0, SPAWN_COLOR),
+            ] {
+                frame.fill_rectangle(
+                    Point::new(center.x - width / 2.0, center.y - height / 2.0),
+                    Size::new(width, height),
+                    to_color(color),
+                );
+            }
+        }
+
+        vec![frame.into_geometry()]
+    }
+}
+
+// The most recent seeds' previews side by side, newest first, with any found seeds picked out
+pub fn view_previews<'a>(previews: &[BiomePreview], found_seeds: &[&str]) -> Row<'a, Message> {
+    previews.iter().fold(Row::new().spacing(16), |row, preview| {
+        let mut label = Text::new(preview.seed.clone()).size(14);
+        if found_seeds.contains(&preview.seed.as_str()) {
+            label = label.color(rules::FIND_COLOR);
+        };
+
+        row.push(
+            Column::new()
+                .push(
+                    Canvas::new(preview.clone())
+                        .width(Length::Units(PREVIEW_CANVAS_SIZE))
+                        .height(Length::Units(PREVIEW_CANVAS_SIZE)),
+                )
+                .push(label)
+                .spacing(4),
+        )
+    })
+}
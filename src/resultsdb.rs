@@ -0,0 +1,434 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use rusqlite::{params, params_from_iter, Connection};
+
+use crate::seedresult::{BiomeSample, ParsedResult, StructureSample};
+
+// Completed results are kept in here when store_results is turned on in config.toml
+pub const RESULTS_DB_PATH: &str = "results.sqlite";
+
+// How many seeds the query command lists unless told otherwise
+const DEFAULT_QUERY_LIMIT: u32 = 50;
+
+// Structures the plugin didn't find have no position. Spawn is where level.dat put it,
+// if the client could read it. searched_at is in seconds since the Unix epoch.
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS seeds (
+        id INTEGER PRIMARY KEY,
+        seed TEXT NOT NULL,
+        minecraft_version TEXT,
+        searched_at INTEGER NOT NULL,
+        spawn_x INTEGER,
+        spawn_y INTEGER,
+        spawn_z INTEGER,
+        raw_result TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS structures (
+        seed_id INTEGER NOT NULL REFERENCES seeds(id),
+        name TEXT NOT NULL,
+        x INTEGER,
+        y INTEGER,
+        z INTEGER
+    );
+    CREATE TABLE IF NOT EXISTS biomes (
+        seed_id INTEGER NOT NULL REFERENCES seeds(id),
+        x INTEGER NOT NULL,
+        z INTEGER NOT NULL,
+        biome TEXT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS structures_by_seed ON structures(seed_id);
+    CREATE INDEX IF NOT EXISTS biomes_by_seed ON biomes(seed_id);
+";
+
+// What to look for among stored seeds. Every condition has to hold for a seed to match.
+pub struct SeedQuery {
+    // Structure name and how many blocks from spawn (or 0,0 if spawn isn't known) it can be
+    pub structures_within: Vec<(String, f64)>,
+    // Biome name and the smallest share of the seed's biome samples it can have, 0 to 1.
+    // The biome has to show up at least once, so 0 means anywhere in the samples.
+    pub biome_shares: Vec<(String, f64)>,
+    pub minecraft_version: Option<String>,
+    pub limit: u32,
+}
+
+pub struct SeedMatch {
+    pub seed: String,
+    pub minecraft_version: Option<String>,
+    // As "YYYY-MM-DD HH:MM:SS", in UTC
+    pub searched_at: String,
+}
+
+// Which stored results to load. Times are in seconds since the Unix epoch, and both ends are included.
+#[derive(Default)]
+pub struct ResultFilter {
+    pub since: Option<i64>,
+    pub until: Option<i64>,
+    pub minecraft_version: Option<String>,
+}
+
+// A result as it was stored, already picked apart
+pub struct StoredResult {
+    pub seed: String,
+    pub minecraft_version: Option<String>,
+    pub searched_at: i64,
+    // The same time as "2022-10-01T18:30:00Z"
+    pub searched_at_utc: String,
+    pub spawn: Option<(i64, i64, i64)>,
+    pub structures: Vec<StructureSample>,
+    pub biomes: Vec<BiomeSample>,
+}
+
+pub struct ResultsDb {
+    connection: Connection,
+}
+
+impl ResultsDb {
+    pub fn open(path: &str) -> Result<ResultsDb, String> {
+        let connection =
+            Connection::open(path).map_err(|e| format!("Unable to open {}: {}", path, e))?;
+
+        connection
+            .execute_batch(SCHEMA)
+            .map_err(|e| format!("Unable to set up {}: {}", path, e))?;
+
+        Ok(ResultsDb { connection })
+    }
+
+    // Stores a raw result along with the samples it was parsed into
+    pub fn store_result(
+        &mut self,
+        seed: &str,
+        minecraft_version: Option<&str>,
+        spawn: Option<(i64, i64, i64)>,
+        raw_result: &str,
+        parsed: &ParsedResult,
+    ) -> Result<(), String> {
+        let searched_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs() as i64;
+
+        let store_error = |e: rusqlite::Error| format!("Unable to store result for {:?}: {}", seed, e);
+
+        let transaction = self.connection.transaction().map_err(store_error)?;
+
+        transaction
+            .execute(
+                "INSERT INTO seeds (seed, minecraft_version, searched_at, spawn_x, spawn_y, spawn_z, raw_result)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                params![
+                    seed,
+                    minecraft_version,
+                    searched_at,
+                    spawn.map(|(x, _, _)| x),
+                    spawn.map(|(_, y, _)| y),
+                    spawn.map(|(_, _, z)| z),
+                    raw_result,
+                ],
+            )
+            .map_err(store_error)?;
+        let seed_id = transaction.last_insert_rowid();
+
+        {
+            let mut insert_structure = transaction
+                .prepare("INSERT INTO structures (seed_id, name, x, y, z) VALUES (?1, ?2, ?3, ?4, ?5)")
+                .map_err(store_error)?;
+            for structure in &parsed.structures {
+                insert_structure
+                    .execute(params![
+                        seed_id,
+                        structure.name,
+                        structure.position.map(|(x, _, _)| x),
+                        structure.position.map(|(_, y, _)| y),
+                        structure.position.map(|(_, _, z)| z),
+                    ])
+                    .map_err(store_error)?;
+            }
+
+            let mut insert_biome = transaction
+                .prepare("INSERT INTO biomes (seed_id, x, z, biome) VALUES (?1, ?2, ?3, ?4)")
+                .map_err(store_error)?;
+            for sample in &parsed.biomes {
+                insert_biome
+                    .execute(params![seed_id, sample.x, sample.z, sample.biome])
+                    .map_err(store_error)?;
+            }
+        }
+
+        transaction.commit().map_err(store_error)
+    }
+
+    // Takes a UTC date ("2022-10-01"), date and time ("2022-10-01 18:30:00"),
+    // or a number of seconds since the Unix epoch, and returns the latter
+    pub fn parse_time(&self, text: &str) -> Result<i64, String> {
+        if let Ok(seconds) = text.parse::<i64>() {
+            return Ok(seconds);
+        }
+
+        self.connection
+            .query_row("SELECT CAST(strftime('%s', ?1) AS INTEGER)", params![text], |row| {
+                row.get::<_, Option<i64>>(0)
+            })
+            .map_err(|e| format!("Unable to read time {:?}: {}", text, e))?
+            .ok_or_else(|| format!("{:?} isn't a date like 2022-10-01 or 2022-10-01 18:30:00", text))
+    }
+
+    // Oldest first
+    pub fn load_results(&self, filter: &ResultFilter) -> Result<Vec<StoredResult>, String> {
+        let load_error = |e: rusqlite::Error| format!("Unable to load results: {}", e);
+
+        let mut select_seeds = self
+            .connection
+            .prepare(
+                "SELECT id, seed, minecraft_version, searched_at, spawn_x, spawn_y, spawn_z,
+                    strftime('%Y-%m-%dT%H:%M:%SZ', searched_at, 'unixepoch')
+                 FROM seeds
+                 WHERE (?1 IS NULL OR searched_at >= ?1)
+                 AND (?2 IS NULL OR searched_at <= ?2)
+                 AND (?3 IS NULL OR minecraft_version = ?3)
+                 ORDER BY searched_at, id",
+            )
+            .map_err(load_error)?;
+        let mut select_structures = self
+            .connection
+            .prepare("SELECT name, x, y, z FROM structures WHERE seed_id = ?1 ORDER BY rowid")
+            .map_err(load_error)?;
+        let mut select_biomes = self
+            .connection
+            .prepare("SELECT x, z, biome FROM biomes WHERE seed_id = ?1 ORDER BY rowid")
+            .map_err(load_error)?;
+
+        let seeds = select_seeds
+            .query_map(
+                params![filter.since, filter.until, filter.minecraft_version],
+                |row| {
+                    let spawn = match (
+                        row.get::<_, Option<i64>>(4)?,
+                        row.get::<_, Option<i64>>(5)?,
+                        row.get::<_, Option<i64>>(6)?,
+                    ) {
+                        (Some(x), Some(y), Some(z)) => Some((x, y, z)),
+                        _ => None,
+                    };
+                    Ok((
+                        row.get::<_, i64>(0)?,
+                        StoredResult {
+                            seed: row.get(1)?,
+                            minecraft_version: row.get(2)?,
+                            searched_at: row.get(3)?,
+                            searched_at_utc: row.get(7)?,
+                            spawn,
+                            structures: vec![],
+                            biomes: vec![],
+                        },
+                    ))
+                },
+            )
+            .map_err(load_error)?
+            .collect::<Result<Vec<(i64, StoredResult)>, rusqlite::Error>>()
+            .map_err(load_error)?;
+
+        let mut results = vec![];
+
+        for (seed_id, mut result) in seeds {
+            result.structures = select_structures
+                .query_map(params![seed_id], |row| {
+                    let position = match (
+                        row.get::<_, Option<i64>>(1)?,
+                        row.get::<_, Option<i64>>(2)?,
+                        row.get::<_, Option<i64>>(3)?,
+                    ) {
+                        (Some(x), Some(y), Some(z)) => Some((x, y, z)),
+                        _ => None,
+                    };
+                    Ok(StructureSample {
+                        name: row.get(0)?,
+                        position,
+                    })
+                })
+                .map_err(load_error)?
+                .collect::<Result<Vec<StructureSample>, rusqlite::Error>>()
+                .map_err(load_error)?;
+
+            result.biomes = select_biomes
+                .query_map(params![seed_id], |row| {
+                    Ok(BiomeSample {
+                        x: row.get(0)?,
+                        z: row.get(1)?,
+                        biome: row.get(2)?,
+                    })
+                })
+                .map_err(load_error)?
+                .collect::<Result<Vec<BiomeSample>, rusqlite::Error>>()
+                .map_err(load_error)?;
+
+            results.push(result);
+        }
+
+        Ok(results)
+    }
+
+    // Names are compared case-insensitively, since the plugin isn't consistent about them.
+    // The conditions mean the same as seedresult::nearest_structure and meets_biome_share,
+    // so a query and a rule with the same conditions agree on which seeds match.
+    pub fn query_seeds(&self, query: &SeedQuery) -> Result<Vec<SeedMatch>, String> {
+        let mut conditions: Vec<String> = vec![];
+        let mut parameters: Vec<rusqlite::types::Value> = vec![];
+
+        for (name, distance) in &query.structures_within {
+            conditions.push(
+                "EXISTS (SELECT 1 FROM structures
+                    WHERE structures.seed_id = seeds.id
+                    AND lower(structures.name) = lower(?)
+                    AND structures.x IS NOT NULL
+                    AND (structures.x - COALESCE(seeds.spawn_x, 0)) * (structures.x - COALESCE(seeds.spawn_x, 0))
+                      + (structures.z - COALESCE(seeds.spawn_z, 0)) * (structures.z - COALESCE(seeds.spawn_z, 0))
+                      <= ?)"
+                    .to_string(),
+            );
+            parameters.push(name.clone().into());
+            parameters.push((distance * distance).into());
+        }
+
+        for (biome, share) in &query.biome_shares {
+            conditions.push(
+                "(SELECT COUNT(*) FROM biomes
+                    WHERE biomes.seed_id = seeds.id AND lower(biomes.biome) = lower(?)) > 0
+                 AND (SELECT COUNT(*) FROM biomes
+                    WHERE biomes.seed_id = seeds.id AND lower(biomes.biome) = lower(?))
+                  >= ? * (SELECT COUNT(*) FROM biomes WHERE biomes.seed_id = seeds.id)"
+                    .to_string(),
+            );
+            parameters.push(biome.clone().into());
+            parameters.push(biome.clone().into());
+            parameters.push((*share).into());
+        }
+
+        if let Some(minecraft_version) = &query.minecraft_version {
+            conditions.push("seeds.minecraft_version = ?".to_string());
+            parameters.push(minecraft_version.clone().into());
+        }
+
+        let where_clause = if conditions.is_empty() {
+            "".to_string()
+        } else {
+            format!("WHERE {}", conditions.join(" AND "))
+        };
+
+        let sql = format!(
+            "SELECT seed, minecraft_version, datetime(searched_at, 'unixepoch') FROM seeds {}
+             ORDER BY searched_at DESC LIMIT {}",
+            where_clause, query.limit
+        );
+
+        let query_error = |e: rusqlite::Error| format!("Unable to query results: {}", e);
+
+        let mut statement = self.connection.prepare(&sql).map_err(query_error)?;
+        let rows = statement
+            .query_map(params_from_iter(parameters), |row| {
+                Ok(SeedMatch {
+                    seed: row.get(0)?,
+                    minecraft_version: row.get(1)?,
+                    searched_at: row.get(2)?,
+                })
+            })
+            .map_err(query_error)?;
+
+        rows.collect::<Result<Vec<SeedMatch>, rusqlite::Error>>()
+            .map_err(query_error)
+    }
+}
+
+// "NAME:VALUE", as taken by --structure and --biome
+fn split_condition<'a>(option: &str, value: &'a str) -> Result<(String, &'a str), String> {
+    match value.rsplit_once(':') {
+        Some((name, amount)) if !name.is_empty() => Ok((name.to_string(), amount)),
+        _ => Err(format!("{} expects NAME:VALUE, not {:?}", option, value)),
+    }
+}
+
+// Shares can be given as a fraction ("0.05") or a percentage ("5%")
+fn parse_share(text: &str) -> Option<f64> {
+    match text.strip_suffix('%') {
+        Some(percentage) => percentage.parse::<f64>().ok().map(|share| share / 100.0),
+        None => text.parse::<f64>().ok(),
+    }
+}
+
+fn parse_query_args(args: &[String]) -> Result<SeedQuery, String> {
+    let mut query = SeedQuery {
+        structures_within: vec![],
+        biome_shares: vec![],
+        minecraft_version: None,
+        limit: DEFAULT_QUERY_LIMIT,
+    };
+
+    let mut args = args.iter();
+    while let Some(option) = args.next() {
+        let value = args
+            .next()
+            .ok_or_else(|| format!("{} needs a value", option))?;
+
+        match option.as_str() {
+            "--structure" => {
+                let (name, distance) = split_condition(option, value)?;
+                let distance = distance
+                    .parse::<f64>()
+                    .map_err(|_| format!("{:?} isn't a distance in blocks", distance))?;
+                query.structures_within.push((name, distance));
+            }
+            "--biome" => {
+                let (name, share) = split_condition(option, value)?;
+                let share = parse_share(share)
+                    .ok_or_else(|| format!("{:?} isn't a share like 0.05 or 5%", share))?;
+                query.biome_shares.push((name, share));
+            }
+            "--version" => query.minecraft_version = Some(value.clone()),
+            "--limit" => {
+                query.limit = value
+                    .parse::<u32>()
+                    .map_err(|_| format!("--limit expects a whole number, not {:?}", value))?;
+            }
+            _ => return Err(format!("Unknown query option: {}", option)),
+        };
+    }
+
+    Ok(query)
+}
+
+// The `query` command, e.g. `query --structure mansion:600 --biome mushroom_fields:5%`
+pub fn run_query_command(args: &[String]) {
+    let query = match parse_query_args(args) {
+        Ok(query) => query,
+        Err(e) => {
+            println!("ERROR: {}", e);
+            println!(
+                "Usage: SpicyGarden query [--structure <name>:<blocks>]... \
+                 [--biome <name>:<share>]... [--version <minecraft version>] [--limit <count>]"
+            );
+            return;
+        }
+    };
+
+    let matches = match ResultsDb::open(RESULTS_DB_PATH).and_then(|db| db.query_seeds(&query)) {
+        Ok(matches) => matches,
+        Err(e) => {
+            println!("ERROR: {}", e);
+            return;
+        }
+    };
+
+    if matches.is_empty() {
+        println!("No stored seeds match.");
+        return;
+    }
+
+    for seed_match in &matches {
+        println!(
+            "{}  {:<10}  {}",
+            seed_match.searched_at,
+            seed_match.minecraft_version.clone().unwrap_or_default(),
+            seed_match.seed
+        );
+    }
+}
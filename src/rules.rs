@@ -0,0 +1,344 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Write;
+use std::process::Command;
+use std::thread;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use iced::{Color, Column, Text};
+use serde::Deserialize;
+
+use crate::seedresult::{self, ParsedResult};
+use crate::Message;
+
+// Every match is appended to this, one per line
+pub const FINDS_FILE_PATH: &str = "finds.txt";
+
+// How many of the latest matches the GUI keeps around
+pub const RECENT_FIND_COUNT: usize = 10;
+
+// Finds are what the whole thing is for, so they stand out
+pub const FIND_COLOR: Color = Color::from_rgb(0.85, 0.45, 0.0);
+
+// A seed worth a closer look, as described by a [[rules]] table in config.toml:
+//
+//     [[rules]]
+//     name = "Jungle village"
+//     structures = { village = 200 }  # within 200 blocks of spawn
+//     biomes = { jungle = 0.1 }       # at least 10% of the biome samples (0 means any at all)
+//     notify = true
+//     command = "curl -d \"$SPICYGARDEN_SEED\" http://localhost:8000/finds"
+//
+// Every condition has to hold for a seed to match.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Rule {
+    pub name: String,
+    // Structure name and how many blocks from spawn (or 0,0 if spawn isn't known) it can be
+    #[serde(default)]
+    pub structures: BTreeMap<String, f64>,
+    // Biome name and the smallest share of the biome samples it can have, 0 to 1.
+    // The biome has to show up at least once, so 0 means anywhere in the samples.
+    #[serde(default)]
+    pub biomes: BTreeMap<String, f64>,
+    // Pops up a desktop notification on a match
+    #[serde(default)]
+    pub notify: bool,
+    // Run through the shell on a match, with the seed and rule in SPICYGARDEN_* variables
+    #[serde(default)]
+    pub command: Option<String>,
+}
+
+impl Rule {
+    // Checks values that parse fine but still can't work
+    pub fn validate(&self) -> Result<(), String> {
+        if self.structures.is_empty() && self.biomes.is_empty() {
+            return Err(format!("rule {:?} has no conditions, so every seed would match", self.name));
+        }
+
+        if let Some((structure, _)) = self.structures.iter().find(|(_, distance)| **distance < 0.0) {
+            return Err(format!(
+                "rule {:?} needs a distance of at least 0 for {:?}",
+                self.name, structure
+            ));
+        }
+
+        if let Some((biome, _)) = self
+            .biomes
+            .iter()
+            .find(|(_, share)| !(0.0..=1.0).contains(*share))
+        {
+            return Err(format!(
+                "rule {:?} needs a share between 0 and 1 for {:?}",
+                self.name, biome
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+// A rule that matched, and why
+#[derive(Debug, Clone)]
+pub struct RuleMatch {
+    pub rule: Rule,
+    pub reasons: Vec<String>,
+}
+
+fn evaluate_rule(rule: &Rule, parsed: &ParsedResult, origin: (i64, i64)) -> Option<RuleMatch> {
+    let mut reasons = vec![];
+
+    for (structure_name, max_distance) in &rule.structures {
+        match seedresult::nearest_structure(&parsed.structures, structure_name, origin) {
+            Some((distance, (x, z))) if distance <= *max_distance => reasons.push(format!(
+                "{} at {},{} ({:.0} blocks from spawn)",
+                structure_name, x, z, distance
+            )),
+            _ => return None,
+        };
+    }
+
+    for (biome_name, min_share) in &rule.biomes {
+        let share = seedresult::biome_share(&parsed.biomes, biome_name);
+        if !seedresult::meets_biome_share(share, *min_share) {
+            return None;
+        }
+
+        reasons.push(format!("{} {:.1}%", biome_name, share * 100.0));
+    }
+
+    Some(RuleMatch {
+        rule: rule.clone(),
+        reasons,
+    })
+}
+
+pub fn evaluate_rules(
+    rules: &[Rule],
+    parsed: &ParsedResult,
+    spawn: Option<(i64, i64, i64)>,
+) -> Vec<RuleMatch> {
+    let origin = seedresult::distance_origin(spawn);
+
+    rules
+        .iter()
+        .filter_map(|rule| evaluate_rule(rule, parsed, origin))
+        .collect()
+}
+
+// Appends "<unix time>\t<seed>\t<rule>\t<reasons>" to the finds file
+fn record_find(seed: &str, rule_match: &RuleMatch) -> Result<(), String> {
+    let found_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+
+    let mut finds_file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(FINDS_FILE_PATH)
+        .map_err(|e| format!("Unable to open {}: {}", FINDS_FILE_PATH, e))?;
+
+    writeln!(
+        finds_file,
+        "{}\t{}\t{}\t{}",
+        found_at,
+        seed,
+        rule_match.rule.name,
+        rule_match.reasons.join(", ")
+    )
+    .map_err(|e| format!("Unable to write {}: {}", FINDS_FILE_PATH, e))
+}
+
+// A double-quoted AppleScript string. Only backslashes and quotes need escaping.
+fn applescript_string(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn notification_command(title: &str, body: &str) -> Option<Command> {
+    if cfg!(target_os = "linux") {
+        let mut command = Command::new("notify-send");
+        command.args([title, body]);
+        Some(command)
+    } else if cfg!(target_os = "macos") {
+        let mut command = Command::new("osascript");
+        command
+            .arg("-e")
+            .arg(format!(
+                "display notification {} with title {}",
+                applescript_string(body),
+                applescript_string(title)
+            ));
+        Some(command)
+    } else {
+        None
+    }
+}
+
+fn shell_command(script: &str) -> Command {
+    if cfg!(windows) {
+        let mut command = Command::new("cmd");
+        command.args(["/C", script]);
+        command
+    } else {
+        let mut command = Command::new("sh");
+        command.args(["-c", script]);
+        command
+    }
+}
+
+// Waits on the command off to the side, so a slow webhook doesn't hold up the search
+fn run_in_background(mut command: Command, description: String) {
+    thread::spawn(move || match command.status() {
+        Ok(status) if status.success() => (),
+        Ok(status) => println!("ERROR: {} exited with {}", description, status),
+        Err(e) => println!("ERROR: Unable to run {}: {:?}", description, e),
+    });
+}
+
+// Logs the match to the finds file and raises whatever alerts its rule asks for
+pub fn handle_match(seed: &str, minecraft_version: Option<&str>, rule_match: &RuleMatch) {
+    println!(
+        "Seed {:?} matches rule {:?}: {}",
+        seed,
+        rule_match.rule.name,
+        rule_match.reasons.join(", ")
+    );
+
+    if let Err(e) = record_find(seed, rule_match) {
+        println!("ERROR: {}", e);
+    };
+
+    if rule_match.rule.notify {
+        let title = format!("SpicyGarden: {}", rule_match.rule.name);
+        let body = format!("Seed {}: {}", seed, rule_match.reasons.join(", "));
+        match notification_command(&title, &body) {
+            Some(command) => run_in_background(command, "desktop notification".to_string()),
+            None => println!("ERROR: Desktop notifications aren't supported on this platform"),
+        };
+    };
+
+    if let Some(script) = &rule_match.rule.command {
+        let mut command = shell_command(script);
+        command
+            .env("SPICYGARDEN_SEED", seed)
+            .env("SPICYGARDEN_RULE", &rule_match.rule.name)
+            .env("SPICYGARDEN_REASONS", rule_match.reasons.join(", "))
+            .env("SPICYGARDEN_MINECRAFT_VERSION", minecraft_version.unwrap_or_default());
+        run_in_background(command, format!("command for rule {:?}", rule_match.rule.name));
+    };
+}
+
+// A match as the GUI remembers it
+#[derive(Debug, Clone)]
+pub struct Find {
+    pub seed: String,
+    pub rule: String,
+    pub reasons: Vec<String>,
+}
+
+// Newest first
+pub fn view_finds<'a>(finds: &[Find]) -> Column<'a, Message> {
+    finds.iter().fold(
+        Column::new()
+            .spacing(4)
+            .push(Text::new("Latest finds").color(FIND_COLOR)),
+        |column, find| {
+            column.push(
+                Text::new(format!(
+                    "{} matches {}: {}",
+                    find.seed,
+                    find.rule,
+                    find.reasons.join(", ")
+                ))
+                .size(16)
+                .color(FIND_COLOR),
+            )
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::seedresult::parse_result;
+
+    fn rule(structures: &[(&str, f64)], biomes: &[(&str, f64)]) -> Rule {
+        Rule {
+            name: "test".to_string(),
+            structures: structures
+                .iter()
+                .map(|(name, distance)| (name.to_string(), *distance))
+                .collect(),
+            biomes: biomes
+                .iter()
+                .map(|(name, share)| (name.to_string(), *share))
+                .collect(),
+            notify: false,
+            command: None,
+        }
+    }
+
+    const RESULT: &str = "Seed: 7
+Structure: (100,64,0) village
+Structure: NOTFOUND MANSION
+Biome: (0,64,0) jungle
+Biome: (16,64,0) plains
+Biome: (32,64,0) plains
+Biome: (48,64,0) plains
+";
+
+    fn matches(rule: Rule) -> bool {
+        !evaluate_rules(&[rule], &parse_result(RESULT), None).is_empty()
+    }
+
+    #[test]
+    fn zero_share_means_present_at_all() {
+        assert!(matches(rule(&[], &[("jungle", 0.0)])));
+        assert!(matches(rule(&[], &[("JUNGLE", 0.25)])));
+        assert!(!matches(rule(&[], &[("desert", 0.0)])));
+        assert!(!matches(rule(&[], &[("jungle", 0.3)])));
+    }
+
+    #[test]
+    fn every_condition_has_to_hold() {
+        assert!(matches(rule(&[("village", 100.0)], &[("plains", 0.5)])));
+        assert!(!matches(rule(&[("village", 99.0)], &[("plains", 0.5)])));
+        assert!(!matches(rule(&[("village", 100.0)], &[("plains", 0.8)])));
+        assert!(!matches(rule(&[("village", 100.0), ("mansion", 1000.0)], &[])));
+    }
+
+    #[test]
+    fn matches_say_why() {
+        let rule_matches = evaluate_rules(
+            &[rule(&[("village", 200.0)], &[("jungle", 0.0)])],
+            &parse_result(RESULT),
+            Some((100, 64, 50)),
+        );
+        assert_eq!(rule_matches.len(), 1);
+        assert_eq!(
+            rule_matches[0].reasons,
+            vec!["village at 100,0 (50 blocks from spawn)", "jungle 25.0%"]
+        );
+    }
+
+    #[test]
+    fn rules_that_cant_work_are_rejected() {
+        assert!(rule(&[], &[]).validate().is_err());
+        assert!(rule(&[("village", -1.0)], &[]).validate().is_err());
+        assert!(rule(&[], &[("jungle", 1.5)]).validate().is_err());
+        assert!(rule(&[], &[("jungle", -0.1)]).validate().is_err());
+        assert!(rule(&[("village", 0.0)], &[("jungle", 1.0)]).validate().is_ok());
+    }
+
+    #[test]
+    fn applescript_strings_are_escaped() {
+        assert_eq!(applescript_string("Seed 42"), "\"Seed 42\"");
+        assert_eq!(
+            applescript_string(r#"say "hi" \ bye"#),
+            r#""say \"hi\" \\ bye""#
+        );
+        // Unicode is left alone, rather than turned into Rust escapes
+        assert_eq!(applescript_string("ünïcode ☃"), "\"ünïcode ☃\"");
+    }
+}
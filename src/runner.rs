@@ -1,28 +1,263 @@
 use std::convert::TryFrom;
+use std::fmt;
 use std::fs;
 use std::process::{Command, Stdio};
-use std::sync::{atomic, mpsc};
+use std::sync::{atomic, mpsc, Arc, Mutex};
 use std::thread;
-use std::time::Duration;
-
+use std::time::{Duration, Instant};
+
+use crate::gatherserver::{GatherServer, Handshake, ResultFormat, CLIENT_VERSION};
+use crate::leveldat::{self, LevelInfo};
+use crate::preview::{self, BiomePreview};
+use crate::resultsdb::{self, ResultsDb};
+use crate::rules::{self, Rule};
+use crate::seedresult::{self, ParsedResult};
+use crate::servertemplate::{self, ReadyTemplate, TemplateFingerprint};
+use crate::terrain::{self, TerrainSummary};
+
+mod healthcheck;
+mod minecraftseed;
+mod rcon;
 mod serverproperties;
 
-// If a server takes this long to complete, then its process will be killed and its seed will be skipped
-const JAVA_TIMEOUT_DURATION: Duration = Duration::from_secs(60);
+use rcon::RconClient;
+
+// Even a server that keeps making progress gets killed after this long, and its seed skipped.
+// Ones that are actually stuck are caught much sooner by the hang detector. This used to be
+// 60s, back when it was the only check, but it has to outlast the detector's longest window
+// (two minutes of log silence while generating) or the detector never gets to decide anything.
+const JAVA_TIMEOUT_DURATION: Duration = Duration::from_secs(300);
+
+// How often to check in on a running server's health
+const HEALTH_PROBE_INTERVAL: Duration = Duration::from_secs(2);
+
+// RCON only comes up once the server is done starting, so there's no point trying before this
+const RCON_FIRST_ATTEMPT_DELAY: Duration = Duration::from_secs(5);
+const RCON_RETRY_DURATION: Duration = Duration::from_secs(2);
+const RCON_TIMEOUT_DURATION: Duration = Duration::from_millis(500);
+
+// How long a server gets to stop on its own after being asked to over RCON
+const RCON_STOP_GRACE_DURATION: Duration = Duration::from_secs(5);
+
+// How long to wait before trying the gather server again after a failed request
+const GATHER_SERVER_RETRY_DURATION: Duration = Duration::from_secs(3);
+
+// Every search gets its own id, so that whoever is listening can tell their event streams apart
+static NEXT_SEARCH_ID: atomic::AtomicU64 = atomic::AtomicU64::new(0);
 
-// All runners will shutdown gracefully when they see this set to true
-pub static JAVA_THREADS_SHUTDOWN: atomic::AtomicBool = atomic::AtomicBool::new(false);
+// Slots held by searches that are still running. A search's slot picks its runner
+// directories and ports, so searches running at the same time stay out of each other's way.
+static SEARCH_SLOTS_IN_USE: Mutex<Vec<u32>> = Mutex::new(vec![]);
 
-// Yeah that's right we're using a global variable to count how many seeds we complete, deal with it
-pub static JAVA_SEED_SEARCH_COUNT: atomic::AtomicU32 = atomic::AtomicU32::new(0);
+// Each search's block of ports only has room for this many runners
+pub const MAX_RUNNER_COUNT: u32 = 100;
+
+// The lowest slot nobody else is using, given back when dropped
+struct SearchSlot(u32);
+
+impl SearchSlot {
+    // None when every slot's ports are already taken
+    fn claim() -> Option<SearchSlot> {
+        let mut slots_in_use = SEARCH_SLOTS_IN_USE.lock().unwrap();
+        let slot =
+            (0..serverproperties::MAX_SEARCH_SLOTS).find(|slot| !slots_in_use.contains(slot))?;
+        slots_in_use.push(slot);
+        Some(SearchSlot(slot))
+    }
+}
+
+impl Drop for SearchSlot {
+    fn drop(&mut self) {
+        SEARCH_SLOTS_IN_USE.lock().unwrap().retain(|slot| *slot != self.0);
+    }
+}
+
+// What a search needs to know from the settings it was started with
+pub struct SearchSettings {
+    pub server_address: String,
+    pub client_key: String,
+    pub runner_count: u32,
+    pub store_results: bool,
+    pub rules: Vec<Rule>,
+}
+
+// Requests sent from the GUI to a running seed search
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RunnerControl {
+    // Let the running servers finish their current seeds, but don't start any new ones
+    Pause,
+    // Start handing out seeds to idle runners again
+    Resume,
+    // Kill all running servers and end the search
+    Stop,
+}
+
+// What a runner is busy doing with its current seed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RunnerPhase {
+    PreparingDirectory,
+    StartingServer,
+    GeneratingWorld,
+    ReadingResults,
+}
+
+impl fmt::Display for RunnerPhase {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let description = match self {
+            RunnerPhase::PreparingDirectory => "Preparing directory",
+            RunnerPhase::StartingServer => "Starting server",
+            RunnerPhase::GeneratingWorld => "Generating world",
+            RunnerPhase::ReadingResults => "Reading results",
+        };
+        write!(f, "{}", description)
+    }
+}
+
+// Everything a seed search reports back to whoever started it
+#[derive(Debug, Clone)]
+pub enum RunnerEvent {
+    SeedAssigned { seed: String },
+    RunnerStarted { runner_index: u32, seed: String },
+    PhaseChanged { runner_index: u32, phase: RunnerPhase },
+    SeedCompleted { runner_index: u32, seed: String },
+    SeedFailed { runner_index: u32, seed: String, reason: String },
+    SeedTimedOut { runner_index: u32, seed: String },
+    PreviewRendered { seed: String, preview: BiomePreview },
+    RuleMatched { seed: String, rule: String, reasons: Vec<String> },
+    SubmissionSucceeded { seed: String },
+    SubmissionFailed { seed: String, reason: String },
+    // Always the last event a search sends
+    SearchStopped,
+}
+
+// Returned by start_seed_search, this is how a search is controlled and observed
+pub struct RunnerHandle {
+    id: u64,
+    control_tx: mpsc::Sender<RunnerControl>,
+    event_rx: Arc<Mutex<mpsc::Receiver<RunnerEvent>>>,
+}
+
+impl RunnerHandle {
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub fn pause(&self) {
+        self.send_control(RunnerControl::Pause);
+    }
+
+    pub fn resume(&self) {
+        self.send_control(RunnerControl::Resume);
+    }
+
+    pub fn stop(&self) {
+        self.send_control(RunnerControl::Stop);
+    }
+
+    // The receiving end of this search's events. It's shared so that it can be
+    // handed off to a subscription that outlives any one borrow of the handle.
+    pub fn event_receiver(&self) -> Arc<Mutex<mpsc::Receiver<RunnerEvent>>> {
+        self.event_rx.clone()
+    }
+
+    fn send_control(&self, control: RunnerControl) {
+        // If the search has already ended, there's nobody to tell
+        let _ = self.control_tx.send(control);
+    }
+}
 
 struct Seed {
     seed: String,
+    // Which Minecraft version the gather server wants this seed run on, if it cares
+    minecraft_version: Option<String>,
     claimed_runner_index: Option<u32>,
     result: Option<String>,
+    // The template this seed was run with
+    fingerprint: Option<TemplateFingerprint>,
+    // What the server said it was while running this seed
+    logged_minecraft_version: Option<String>,
+    // What the world's level.dat had to say once the server was done with it
+    level_info: Option<LevelInfo>,
+    // Worked out from the chunks generated around spawn
+    terrain: Option<TerrainSummary>,
+    // The result picked apart, once, for everything that needs more than the raw text
+    parsed_result: Option<ParsedResult>,
+    // The biome map drawn from the plugin's samples, and the PNG it was saved as
+    preview: Option<BiomePreview>,
+    preview_png: Option<Vec<u8>>,
+}
+
+impl Seed {
+    // The best idea we have of which Minecraft version actually ran this seed
+    fn ran_minecraft_version(&self) -> Option<String> {
+        self.fingerprint
+            .as_ref()
+            .and_then(|fingerprint| fingerprint.minecraft_version.clone())
+            .or_else(|| self.logged_minecraft_version.clone())
+            .or_else(|| self.minecraft_version.clone())
+    }
+}
+
+// Assignments are one seed per line, optionally followed by a tab and the Minecraft version to run it on
+fn parse_assignment(line: &str) -> Seed {
+    let (seed, minecraft_version) = match line.rsplit_once('\t') {
+        Some((seed, version)) => (seed, Some(version.trim().to_string())),
+        None => (line, None),
+    };
+
+    Seed {
+        seed: seed.to_string(),
+        minecraft_version,
+        claimed_runner_index: None,
+        result: None,
+        fingerprint: None,
+        logged_minecraft_version: None,
+        level_info: None,
+        terrain: None,
+        parsed_result: None,
+        preview: None,
+        preview_png: None,
+    }
+}
+
+// Logs a runner's failure, lets the listener know about it, and hands the seed back
+fn runner_failed(events: &mpsc::Sender<RunnerEvent>, target_seed: Seed, reason: String) -> Seed {
+    println!("ERROR: {}", reason);
+    let _ = events.send(RunnerEvent::SeedFailed {
+        runner_index: target_seed.claimed_runner_index.unwrap(),
+        seed: target_seed.seed.clone(),
+        reason,
+    });
+    target_seed
+}
+
+// Asks the server to stop over RCON if we can, and kills it if it doesn't (or we can't)
+fn stop_server(server_process: &mut std::process::Child, rcon: Option<&mut RconClient>) {
+    if let Some(rcon) = rcon {
+        if rcon.command("stop").is_ok() {
+            let asked_at = Instant::now();
+            while asked_at.elapsed() < RCON_STOP_GRACE_DURATION {
+                if let Ok(Some(_status)) = server_process.try_wait() {
+                    return;
+                }
+                thread::sleep(Duration::from_millis(100));
+            }
+        };
+    };
+
+    // If killing fails, the process already died
+    if server_process.kill().is_ok() {
+        server_process.wait().unwrap(); // Wait to ensure resources are released
+    };
 }
 
-fn run_server(mut target_seed: Seed) -> Seed {
+fn run_server(
+    mut target_seed: Seed,
+    search_slot: u32,
+    templates: Arc<Vec<ReadyTemplate>>,
+    shutdown: Arc<atomic::AtomicBool>,
+    events: mpsc::Sender<RunnerEvent>,
+) -> Seed {
     println!(
         "Running server {} with seed {}",
         target_seed.claimed_runner_index.unwrap(),
@@ -30,19 +265,41 @@ fn run_server(mut target_seed: Seed) -> Seed {
     );
 
     let runner_index = target_seed.claimed_runner_index.unwrap();
-    let runner_dir = format!("runners/runner_{}", runner_index);
+    let runner_dir = format!("runners/search_{}/runner_{}", search_slot, runner_index);
+
+    let set_phase = |phase: RunnerPhase| {
+        let _ = events.send(RunnerEvent::PhaseChanged {
+            runner_index,
+            phase,
+        });
+    };
+
+    set_phase(RunnerPhase::PreparingDirectory);
+
+    let ready_template =
+        match servertemplate::select_template(&templates, target_seed.minecraft_version.as_deref()) {
+            Some(ready_template) => ready_template,
+            None => {
+                let reason = format!(
+                    "No server template for Minecraft {}",
+                    target_seed.minecraft_version.clone().unwrap_or_default()
+                );
+                return runner_failed(&events, target_seed, reason);
+            }
+        };
+    let template = &ready_template.template;
+    target_seed.fingerprint = Some(ready_template.fingerprint.clone());
 
     // Clean up previous runner's server
     _ = fs::remove_dir_all(runner_dir.clone());
     match fs::create_dir_all(runner_dir.clone()) {
         Ok(_) => (),
         Err(e) => {
-            println!(
-                "ERROR: Unable to create directory {} - {:?}",
-                runner_dir.clone(),
-                e
+            return runner_failed(
+                &events,
+                target_seed,
+                format!("Unable to create directory {} - {:?}", runner_dir, e),
             );
-            return target_seed;
         }
     };
 
@@ -50,46 +307,67 @@ fn run_server(mut target_seed: Seed) -> Seed {
     let mut copy_options = fs_extra::dir::CopyOptions::new();
     copy_options.copy_inside = true;
     copy_options.content_only = true;
-    match fs_extra::dir::copy("server_template", runner_dir.clone(), &copy_options) {
+    match fs_extra::dir::copy(&template.dir, runner_dir.clone(), &copy_options) {
         Ok(_) => (),
         Err(e) => {
-            println!(
-                "ERROR: Unable to copy server_template into {} - {:?}",
-                runner_dir, e
+            return runner_failed(
+                &events,
+                target_seed,
+                format!(
+                    "Unable to copy {} into {} - {:?}",
+                    template.dir, runner_dir, e
+                ),
             );
-            return target_seed;
         }
     };
 
     // Write a seed-specific (and runner-specific) server.properties
-    let server_properties: String =
-        serverproperties::get_server_properties(runner_index, &target_seed.seed.clone());
+    let rcon_password = match rcon::generate_password() {
+        Ok(rcon_password) => rcon_password,
+        Err(e) => return runner_failed(&events, target_seed, e),
+    };
+    let server_properties: String = serverproperties::get_server_properties(
+        search_slot,
+        runner_index,
+        &target_seed.seed,
+        &rcon_password,
+    );
     match fs::write(
         format!("{}/server.properties", runner_dir.clone()),
         server_properties,
     ) {
         Ok(_) => (),
         Err(e) => {
-            println!("ERROR: Unable to write to server.properties - {:?}", e);
-            return target_seed;
+            return runner_failed(
+                &events,
+                target_seed,
+                format!("Unable to write to server.properties - {:?}", e),
+            );
         }
     };
 
+    set_phase(RunnerPhase::StartingServer);
+
     // Start the java server in a child process
-    let mut server_process: std::process::Child = match Command::new("java")
+    let mut server_process: std::process::Child = match Command::new(&template.java)
         .current_dir(runner_dir.clone())
-        .args(["-Xms32M", "-Xmx512M", "-jar", "server.jar", "nogui"])
+        .args(["-Xms32M", "-Xmx512M", "-jar", &template.jar, "nogui"])
         .stdin(Stdio::null())
         .stdout(Stdio::null())
         .spawn()
     {
         Ok(process) => process,
         Err(e) => {
-            println!("ERROR: Unable to start minecraft server: {:?}", e);
-            return target_seed;
+            return runner_failed(
+                &events,
+                target_seed,
+                format!("Unable to start minecraft server: {:?}", e),
+            );
         }
     };
 
+    set_phase(RunnerPhase::GeneratingWorld);
+
     // Elaborate busy loop because rust doesn't help you timeout child processes
     let (timeout_tx, timeout_rx) = mpsc::channel();
 
@@ -98,47 +376,108 @@ fn run_server(mut target_seed: Seed) -> Seed {
         let _ = timeout_tx.send("timeout");
     });
 
+    // The plugin normally shuts the server down before RCON ever comes up, so getting
+    // a connection at all means the server is sitting around when it shouldn't be
+    let mut rcon: Option<RconClient> = None;
+    let mut next_rcon_attempt = Instant::now() + RCON_FIRST_ATTEMPT_DELAY;
+
+    let mut hang_detector =
+        healthcheck::HangDetector::new(serverproperties::server_port(search_slot, runner_index), &runner_dir);
+    let mut next_health_probe = Instant::now() + HEALTH_PROBE_INTERVAL;
+
     loop {
-        if JAVA_THREADS_SHUTDOWN.load(atomic::Ordering::Relaxed) {
-            match server_process.kill() {
-                Ok(()) => {
-                    server_process.wait().unwrap(); // Wait to ensure resources are released
-                }
-                Err(_) => (), // Process already died
-            };
+        if shutdown.load(atomic::Ordering::Relaxed) {
+            stop_server(&mut server_process, rcon.as_mut());
             return target_seed;
         }
 
+        if rcon.is_none() && Instant::now() >= next_rcon_attempt {
+            match RconClient::connect(
+                serverproperties::rcon_port(search_slot, runner_index),
+                &rcon_password,
+                RCON_TIMEOUT_DURATION,
+            ) {
+                Ok(mut client) => {
+                    println!("Runner {} is up and answering RCON", runner_index);
+
+                    // Catch a wrong world now, rather than after waiting out the timeout
+                    let expected_seed = minecraftseed::resolve_seed(&target_seed.seed);
+                    let running_seed = client
+                        .command("seed")
+                        .ok()
+                        .and_then(|response| minecraftseed::parse_seed_command(&response));
+
+                    if let (Some(expected_seed), Some(running_seed)) = (expected_seed, running_seed) {
+                        if running_seed != expected_seed {
+                            stop_server(&mut server_process, Some(&mut client));
+                            let reason = format!(
+                                "Runner {} is running seed {} instead of {} for {:?}",
+                                runner_index, running_seed, expected_seed, target_seed.seed
+                            );
+                            return runner_failed(&events, target_seed, reason);
+                        };
+                    };
+
+                    rcon = Some(client);
+                }
+                Err(_) => next_rcon_attempt = Instant::now() + RCON_RETRY_DURATION,
+            };
+        };
+
+        if Instant::now() >= next_health_probe {
+            next_health_probe = Instant::now() + HEALTH_PROBE_INTERVAL;
+
+            if let Some(reason) = hang_detector.probe(rcon.as_mut()) {
+                println!("TIMEOUT: Runner {} looks stuck ({}), giving up", runner_index, reason);
+                stop_server(&mut server_process, rcon.as_mut());
+                let _ = events.send(RunnerEvent::SeedTimedOut {
+                    runner_index,
+                    seed: target_seed.seed.clone(),
+                });
+                return target_seed;
+            };
+        };
+
         match server_process.try_wait() {
             Ok(Some(status)) => {
                 if status.success() {
                     break;
                 } else {
-                    println!("ERROR: Minecraft server exited with failure: {}", status);
-                    return target_seed;
+                    return runner_failed(
+                        &events,
+                        target_seed,
+                        format!("Minecraft server exited with failure: {}", status),
+                    );
                 }
             }
             Ok(None) => {
                 thread::sleep(Duration::from_millis(10));
             }
             Err(e) => {
-                println!("ERROR: Failed while waiting on java process: {:?}", e);
-                return target_seed;
+                return runner_failed(
+                    &events,
+                    target_seed,
+                    format!("Failed while waiting on java process: {:?}", e),
+                );
             }
         }
 
         match timeout_rx.try_recv() {
             Ok(_timeout) => {
+                let responsiveness = match rcon.as_mut().map(|client| client.is_alive()) {
+                    Some(true) => "still answering RCON, so the plugin never finished",
+                    Some(false) => "no longer answering RCON",
+                    None => "never answered RCON",
+                };
                 println!(
-                    "TIMEOUT: Runner {} exceeded timeout, giving up",
-                    runner_index
+                    "TIMEOUT: Runner {} exceeded timeout ({}), giving up",
+                    runner_index, responsiveness
                 );
-                match server_process.kill() {
-                    Ok(()) => {
-                        server_process.wait().unwrap(); // Wait to ensure resources are released
-                    }
-                    Err(_) => (), // Process already died
-                };
+                stop_server(&mut server_process, rcon.as_mut());
+                let _ = events.send(RunnerEvent::SeedTimedOut {
+                    runner_index,
+                    seed: target_seed.seed.clone(),
+                });
                 return target_seed;
             }
             Err(_e) => (),
@@ -147,34 +486,194 @@ fn run_server(mut target_seed: Seed) -> Seed {
 
     // Check for results
 
+    set_phase(RunnerPhase::ReadingResults);
+
     let server_result = match fs::read(format!("{}/SpicyGardenData.txt", runner_dir)) {
         Ok(result_txt) => result_txt,
         Err(e) => {
-            println!(
-                "ERROR: Unable to read SpicyGardenData.txt on runner {}: {:?}",
-                runner_index, e
+            return runner_failed(
+                &events,
+                target_seed,
+                format!(
+                    "Unable to read SpicyGardenData.txt on runner {}: {:?}",
+                    runner_index, e
+                ),
             );
-            return target_seed;
         }
     };
 
     let decoded_server_result = match String::from_utf8(server_result) {
         Ok(decoded) => decoded,
         Err(e) => {
-            println!(
-                "ERROR: Failed to decode SpicyGardenData.txt on runner {}: {:?}",
-                runner_index, e
+            return runner_failed(
+                &events,
+                target_seed,
+                format!(
+                    "Failed to decode SpicyGardenData.txt on runner {}: {:?}",
+                    runner_index, e
+                ),
             );
-            return target_seed;
         }
     };
 
+    // Make sure the world we read about is the one we asked for, and not something left over
+    // in the template or generated from a server.properties that didn't get through intact
+    let expected_seed = minecraftseed::resolve_seed(&target_seed.seed);
+
+    if let Some(expected_seed) = expected_seed {
+        match minecraftseed::reported_seed(&decoded_server_result) {
+            Some(reported_seed) if reported_seed == expected_seed => (),
+            Some(reported_seed) => {
+                let reason = format!(
+                    "Runner {} generated seed {} instead of {} for {:?}, discarding its results",
+                    runner_index, reported_seed, expected_seed, target_seed.seed
+                );
+                return runner_failed(&events, target_seed, reason);
+            }
+            None => {
+                return runner_failed(
+                    &events,
+                    target_seed,
+                    format!(
+                        "SpicyGardenData.txt on runner {} doesn't say which seed it's for",
+                        runner_index
+                    ),
+                );
+            }
+        };
+    };
+
+    // level.dat is saved as the server shuts down, and knows a few things the plugin doesn't report
+    let level_info = match leveldat::read_level_info(&format!("{}/world", runner_dir)) {
+        Ok(level_info) => Some(level_info),
+        Err(e) => {
+            println!("ERROR: Runner {}: {}", runner_index, e);
+            None
+        }
+    };
+
+    // It also keeps its own copy of the seed, which had better agree
+    let level_seed = level_info.as_ref().and_then(|level_info| level_info.seed);
+    if let (Some(expected_seed), Some(level_seed)) = (expected_seed, level_seed) {
+        if level_seed != expected_seed {
+            let reason = format!(
+                "Runner {}'s level.dat has seed {} instead of {} for {:?}, discarding its results",
+                runner_index, level_seed, expected_seed, target_seed.seed
+            );
+            return runner_failed(&events, target_seed, reason);
+        };
+    };
+
+    // The server has generated real chunks around spawn, which say more than the plugin's samples
+    let spawn = level_info.as_ref().and_then(|level_info| level_info.spawn);
+    let terrain = spawn.and_then(|spawn| {
+        match terrain::summarize_terrain(&format!("{}/world", runner_dir), spawn) {
+            Ok(terrain) => Some(terrain),
+            Err(e) => {
+                println!("ERROR: Runner {}: {}", runner_index, e);
+                None
+            }
+        }
+    });
+
+    let parsed_result = seedresult::parse_result(&decoded_server_result);
+    match preview::render_preview(&target_seed.seed, &parsed_result, spawn) {
+        Ok((biome_preview, png_bytes)) => {
+            if let Err(e) = preview::save_preview(&target_seed.seed, &png_bytes) {
+                println!("ERROR: Runner {}: {}", runner_index, e);
+            };
+            target_seed.preview = Some(biome_preview);
+            target_seed.preview_png = Some(png_bytes);
+        }
+        Err(e) => println!("ERROR: Runner {}: {}", runner_index, e),
+    };
+
     target_seed.result = Some(decoded_server_result);
+    target_seed.parsed_result = Some(parsed_result);
+    target_seed.level_info = level_info;
+    target_seed.terrain = terrain;
+    target_seed.logged_minecraft_version = servertemplate::read_log_minecraft_version(&runner_dir);
 
-    return target_seed;
+    target_seed
 }
 
-fn seed_search_loop(gather_server_address: String, client_key: String, target_runner_count: u32) {
+// Header values have to be visible ASCII, and world names in particular needn't be
+fn header_safe(value: &str) -> String {
+    value
+        .chars()
+        .filter(|c| c.is_ascii_graphic() || *c == ' ')
+        .collect()
+}
+
+// Puts a seed's result in whichever shape the gather server asked for, along with its content type
+fn submission_body(result_format: ResultFormat, seed: &Seed) -> (String, &'static str) {
+    let raw = seed.result.clone().unwrap();
+
+    if result_format == ResultFormat::Raw {
+        return (raw, "text/plain");
+    }
+
+    // Every seed with a result was parsed along with it
+    let parsed_result = seed.parsed_result.as_ref().unwrap();
+    let spawn = seed.level_info.as_ref().and_then(|level_info| level_info.spawn);
+    let summary = seedresult::summarize_result(parsed_result, spawn);
+
+    let body = match result_format {
+        ResultFormat::Summary => serde_json::to_string(&summary),
+        _ => serde_json::to_string(&serde_json::json!({ "raw": raw, "summary": summary })),
+    };
+
+    (body.unwrap(), "application/json")
+}
+
+// Previews are nice to have, so a failed upload is logged and left at that rather than retried
+fn submit_preview(gather_server: &mut GatherServer, seed: &str, png_bytes: &[u8]) {
+    let response = gather_server.post("submit_preview", &[]).and_then(|request| {
+        request
+            .header("SpicyGarden-Seed", seed)
+            .header(reqwest::header::CONTENT_TYPE, "image/png")
+            .body(png_bytes.to_vec())
+            .send()
+    });
+
+    match response {
+        Ok(r) if r.status() == 204 => (),
+        Ok(r) => println!(
+            "ERROR: Gather server responded with {} to seed {:?}'s preview",
+            r.status(),
+            seed
+        ),
+        Err(e) => println!("ERROR: Unable to send seed {:?}'s preview: {:?}", seed, e),
+    };
+}
+
+fn seed_search_loop(
+    settings: SearchSettings,
+    search_slot: &SearchSlot,
+    handshake: Handshake,
+    templates: Vec<ReadyTemplate>,
+    control_rx: mpsc::Receiver<RunnerControl>,
+    events: mpsc::Sender<RunnerEvent>,
+) {
+    let SearchSettings {
+        server_address: gather_server_address,
+        client_key,
+        runner_count: target_runner_count,
+        store_results,
+        rules,
+    } = settings;
+    let search_slot = search_slot.0;
+
+    // The settings are validated before a search starts, but more runners than this would
+    // take ports from the next slot's block
+    if target_runner_count > MAX_RUNNER_COUNT {
+        println!(
+            "ERROR: Only running {} of the {} runners asked for",
+            MAX_RUNNER_COUNT, target_runner_count
+        );
+    }
+    let target_runner_count = target_runner_count.min(MAX_RUNNER_COUNT);
+
     let mut halted_runners: Vec<u32> = (0..target_runner_count).collect();
 
     let mut seed_pool: Vec<Seed> = vec![];
@@ -182,12 +681,47 @@ fn seed_search_loop(gather_server_address: String, client_key: String, target_ru
 
     let (tx, rx) = mpsc::channel::<Seed>();
 
-    let http_client = reqwest::blocking::Client::new();
+    let mut gather_server = GatherServer::new(&gather_server_address, &client_key);
+
+    // Runners kill their servers and halt when they see this set to true
+    let shutdown = Arc::new(atomic::AtomicBool::new(false));
+
+    let templates = Arc::new(templates);
+
+    // Searching carries on without it, since the gather server still gets every result
+    let mut results_db = if store_results {
+        match ResultsDb::open(resultsdb::RESULTS_DB_PATH) {
+            Ok(results_db) => Some(results_db),
+            Err(e) => {
+                println!("ERROR: {}", e);
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    let mut paused = false;
+    let mut next_submission_attempt = Instant::now();
 
     loop {
-        // If shutdown has been signaled, wait for all runners to complete and then break
+        // Apply any requests that have come in from the GUI
+
+        let stop_requested = loop {
+            match control_rx.try_recv() {
+                Ok(RunnerControl::Pause) => paused = true,
+                Ok(RunnerControl::Resume) => paused = false,
+                Ok(RunnerControl::Stop) => break true,
+                Err(mpsc::TryRecvError::Empty) => break false,
+                // Nobody is left to tell us what to do, so don't keep going forever
+                Err(mpsc::TryRecvError::Disconnected) => break true,
+            }
+        };
+
+        // If a stop has been requested, kill all runners, wait for them to halt, and then break
 
-        if JAVA_THREADS_SHUTDOWN.load(atomic::Ordering::Relaxed) {
+        if stop_requested {
+            shutdown.store(true, atomic::Ordering::Relaxed);
             while halted_runners.len() < target_runner_count as usize {
                 let received = rx.recv().unwrap_or_else(|error| {
                     panic!("Thread communication error: {:?}", error);
@@ -198,108 +732,235 @@ fn seed_search_loop(gather_server_address: String, client_key: String, target_ru
             break;
         }
 
-        // Make sure we've got seeds from the gather server in the pool
+        if !paused {
+            // Make sure we've got seeds from the gather server in the pool
 
-        let seed_pool_count: u32 = u32::try_from(seed_pool.len()).unwrap();
+            let seed_pool_count: u32 = u32::try_from(seed_pool.len()).unwrap();
 
-        if seed_pool_count < target_runner_count {
-            let requested_seed_count = target_runner_count - seed_pool_count;
-            let request_uri = format!(
-                "{}/assign_seeds/{}/{}",
-                gather_server_address, client_key, requested_seed_count,
-            );
+            if seed_pool_count < target_runner_count {
+                let requested_seed_count = target_runner_count - seed_pool_count;
 
-            let response = match http_client.get(request_uri).send() {
-                Ok(r) => r,
-                Err(e) => {
-                    println!("Unable to contact seed server: {:?} Retrying in 3s.", e);
-                    thread::sleep(Duration::from_secs(3));
+                let response = match gather_server
+                    .get("assign_seeds", &[&requested_seed_count.to_string()])
+                    .and_then(|request| request.send())
+                {
+                    Ok(r) => r,
+                    Err(e) => {
+                        println!("Unable to contact seed server: {:?} Retrying in 3s.", e);
+                        thread::sleep(GATHER_SERVER_RETRY_DURATION);
+                        continue;
+                    }
+                };
+
+                if response.status() != 200 {
+                    println!(
+                        "Bad response from seed server: {:?} Retrying in 3s.",
+                        response.status()
+                    );
+                    thread::sleep(GATHER_SERVER_RETRY_DURATION);
                     continue;
                 }
-            };
 
-            if response.status() != 200 {
-                println!(
-                    "Bad response from seed server: {:?} Retrying in 3s.",
-                    response.status()
-                );
-                thread::sleep(Duration::from_secs(3));
-                continue;
-            }
+                let body_text = response.text().unwrap();
+                let assigned_seeds: Vec<&str> = body_text.split("\n").collect();
 
-            let body_text = response.text().unwrap();
-            let assigned_seeds: Vec<&str> = body_text.split("\n").collect();
+                for line in assigned_seeds {
+                    let new_seed = parse_assignment(line);
 
-            for seed in assigned_seeds {
-                let new_seed = Seed {
-                    seed: seed.to_string(),
-                    claimed_runner_index: None,
-                    result: None,
-                };
-                seed_pool.push(new_seed);
+                    let _ = events.send(RunnerEvent::SeedAssigned {
+                        seed: new_seed.seed.clone(),
+                    });
+
+                    seed_pool.push(new_seed);
+                }
             }
-        }
 
-        // Spawn new runners if one or more has halted
+            // Spawn new runners if one or more has halted
 
-        while halted_runners.len() > 0 {
-            let runner_index = halted_runners.pop().unwrap();
+            while let Some(runner_index) = halted_runners.pop() {
 
-            let mut seed = seed_pool.pop().unwrap();
-            seed.claimed_runner_index = Some(runner_index);
+                let mut seed = seed_pool.pop().unwrap();
+                seed.claimed_runner_index = Some(runner_index);
 
-            let runner_tx = tx.clone();
+                let _ = events.send(RunnerEvent::RunnerStarted {
+                    runner_index,
+                    seed: seed.seed.clone(),
+                });
 
-            thread::spawn(move || {
-                runner_tx.send(run_server(seed)).unwrap();
-            });
+                let runner_tx = tx.clone();
+                let runner_templates = templates.clone();
+                let runner_shutdown = shutdown.clone();
+                let runner_events = events.clone();
+
+                thread::spawn(move || {
+                    runner_tx
+                        .send(run_server(
+                            seed,
+                            search_slot,
+                            runner_templates,
+                            runner_shutdown,
+                            runner_events,
+                        ))
+                        .unwrap();
+                });
+            }
         }
 
         // Submit completed seeds to the gather server if we have any
 
-        while completed_seeds.len() > 0 {
+        while !completed_seeds.is_empty() && Instant::now() >= next_submission_attempt {
             let seed = completed_seeds.pop().unwrap();
 
-            let request_uri = format!("{}/submit_result/{}", gather_server_address, client_key,);
+            // Only seeds that ran to completion get this far, and they always have a fingerprint
+            let fingerprint = seed.fingerprint.clone().unwrap();
+
+            let minecraft_version = seed.ran_minecraft_version();
 
-            let _response = match http_client
-                .post(request_uri)
-                .header("SpicyGarden-Seed", seed.seed.clone())
-                .body(seed.result.clone().unwrap())
-                .send()
-            {
+            let (body, content_type) = submission_body(handshake.result_format, &seed);
+
+            match gather_server.post("submit_result", &[]).and_then(|request| {
+                let mut request = request
+                    .header("SpicyGarden-Seed", seed.seed.clone())
+                    .header("SpicyGarden-Result-Format", handshake.result_format.name())
+                    .header("SpicyGarden-Client-Version", CLIENT_VERSION)
+                    .header("SpicyGarden-Server-Jar-SHA256", fingerprint.server_jar_sha256.clone())
+                    .header("SpicyGarden-Plugin-SHA256", fingerprint.plugin_sha256.clone());
+
+                if let Some(minecraft_version) = &minecraft_version {
+                    request = request.header("SpicyGarden-Minecraft-Version", minecraft_version.clone());
+                }
+
+                if let Some(plugin_version) = &fingerprint.plugin_version {
+                    request = request.header("SpicyGarden-Plugin-Version", plugin_version.clone());
+                }
+
+                if let Some(level_info) = &seed.level_info {
+                    if let Some((x, y, z)) = level_info.spawn {
+                        request = request.header("SpicyGarden-Spawn", format!("{},{},{}", x, y, z));
+                    }
+                    if let Some(data_version) = level_info.data_version {
+                        request = request.header("SpicyGarden-Data-Version", data_version.to_string());
+                    }
+                    if let Some(generator) = &level_info.generator {
+                        request = request.header("SpicyGarden-Generator", header_safe(generator));
+                    }
+                    if let Some(level_name) = &level_info.level_name {
+                        request = request.header("SpicyGarden-Level-Name", header_safe(level_name));
+                    }
+                }
+
+                if let Some(terrain) = &seed.terrain {
+                    request = request
+                        .header("SpicyGarden-Terrain", terrain.header_value())
+                        .header(
+                            "SpicyGarden-Surface-Biomes",
+                            header_safe(&terrain.surface_biomes_header_value()),
+                        );
+                }
+
+                request
+                    .header(reqwest::header::CONTENT_TYPE, content_type)
+                    .body(body)
+                    .send()
+            }) {
                 Ok(r) => {
                     if r.status() != 204 {
                         println!("Error submitting to seed server: {:?}", r.status());
+                        let _ = events.send(RunnerEvent::SubmissionFailed {
+                            seed: seed.seed.clone(),
+                            reason: format!("Gather server responded with {}", r.status()),
+                        });
                         completed_seeds.push(seed);
+                        next_submission_attempt = Instant::now() + GATHER_SERVER_RETRY_DURATION;
                         break;
                     }
                     println!("Sent result for seed: {:?}", seed.seed.clone());
 
-                    // Communicate the completed seed to the GUI
-                    JAVA_SEED_SEARCH_COUNT.fetch_add(1, atomic::Ordering::Relaxed);
+                    if handshake.accepts_previews {
+                        if let Some(png_bytes) = &seed.preview_png {
+                            submit_preview(&mut gather_server, &seed.seed, png_bytes);
+                        };
+                    };
+
+                    let _ = events.send(RunnerEvent::SubmissionSucceeded {
+                        seed: seed.seed.clone(),
+                    });
                 }
                 Err(e) => {
                     println!("Unable to submit to seed server: {:?}", e);
+                    let _ = events.send(RunnerEvent::SubmissionFailed {
+                        seed: seed.seed.clone(),
+                        reason: format!("Unable to contact gather server: {}", e),
+                    });
                     completed_seeds.push(seed);
+                    next_submission_attempt = Instant::now() + GATHER_SERVER_RETRY_DURATION;
                     break;
                 }
             };
         }
 
-        // Check for completed runners
+        // Check for completed runners, checking back in with the GUI every so often
 
-        let received = rx.recv().unwrap_or_else(|error| {
-            panic!("Thread communication error: {:?}", error);
-        });
+        if halted_runners.len() == target_runner_count as usize {
+            // Every runner is idle because we're paused, so there's nothing to wait on
+            thread::sleep(Duration::from_millis(100));
+            continue;
+        }
+
+        let received = match rx.recv_timeout(Duration::from_millis(100)) {
+            Ok(received) => received,
+            Err(mpsc::RecvTimeoutError::Timeout) => continue,
+            Err(error) => panic!("Thread communication error: {:?}", error),
+        };
 
         halted_runners.push(received.claimed_runner_index.unwrap());
 
-        match &received.result {
-            Some(_result) => {
+        match (&received.result, &received.parsed_result) {
+            (Some(result), Some(parsed_result)) => {
                 println!("Seed {:?} completed successfully.", received.seed);
+
+                let spawn = received.level_info.as_ref().and_then(|level_info| level_info.spawn);
+
+                if let Some(results_db) = &mut results_db {
+                    if let Err(e) = results_db.store_result(
+                        &received.seed,
+                        received.ran_minecraft_version().as_deref(),
+                        spawn,
+                        result,
+                        parsed_result,
+                    ) {
+                        println!("ERROR: {}", e);
+                    };
+                };
+
+                let _ = events.send(RunnerEvent::SeedCompleted {
+                    runner_index: received.claimed_runner_index.unwrap(),
+                    seed: received.seed.clone(),
+                });
+
+                if let Some(preview) = &received.preview {
+                    let _ = events.send(RunnerEvent::PreviewRendered {
+                        seed: received.seed.clone(),
+                        preview: preview.clone(),
+                    });
+                };
+
+                if !rules.is_empty() {
+                    for rule_match in rules::evaluate_rules(&rules, parsed_result, spawn) {
+                        rules::handle_match(
+                            &received.seed,
+                            received.ran_minecraft_version().as_deref(),
+                            &rule_match,
+                        );
+                        let _ = events.send(RunnerEvent::RuleMatched {
+                            seed: received.seed.clone(),
+                            rule: rule_match.rule.name,
+                            reasons: rule_match.reasons,
+                        });
+                    }
+                };
             }
-            None => {
+            _ => {
                 println!("Seed {:?} failed to capture data. Skipping.", received.seed);
                 continue;
             }
@@ -309,8 +970,44 @@ fn seed_search_loop(gather_server_address: String, client_key: String, target_ru
     }
 }
 
-// Async wrapper function because async closures aren't stable yet
-// (and the main function doesn't need to all be async)
-pub async fn seed_search_async_wrapper(server_address: String, client_key: String, runner_count: u32) {
-    seed_search_loop(server_address, client_key, runner_count);
+// Starts searching seeds on a thread of its own. Any number of searches can be
+// running at once, each one controlled and observed through its own handle.
+pub fn start_seed_search(
+    settings: SearchSettings,
+    handshake: Handshake,
+    templates: Vec<ReadyTemplate>,
+) -> RunnerHandle {
+    let (control_tx, control_rx) = mpsc::channel::<RunnerControl>();
+    let (event_tx, event_rx) = mpsc::channel::<RunnerEvent>();
+
+    thread::spawn(move || {
+        // Held until every runner has halted, so the next search can't reuse its ports early
+        let search_slot = match SearchSlot::claim() {
+            Some(search_slot) => search_slot,
+            None => {
+                println!(
+                    "ERROR: Unable to start another search, {} are already running",
+                    serverproperties::MAX_SEARCH_SLOTS
+                );
+                let _ = event_tx.send(RunnerEvent::SearchStopped);
+                return;
+            }
+        };
+        seed_search_loop(
+            settings,
+            &search_slot,
+            handshake,
+            templates,
+            control_rx,
+            event_tx.clone(),
+        );
+        drop(search_slot);
+        let _ = event_tx.send(RunnerEvent::SearchStopped);
+    });
+
+    RunnerHandle {
+        id: NEXT_SEARCH_ID.fetch_add(1, atomic::Ordering::Relaxed),
+        control_tx,
+        event_rx: Arc::new(Mutex::new(event_rx)),
+    }
 }
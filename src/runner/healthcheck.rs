@@ -0,0 +1,310 @@
+use std::fs;
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpStream, UdpSocket};
+use std::time::{Duration, Instant};
+
+use super::rcon::RconClient;
+
+const PROBE_TIMEOUT_DURATION: Duration = Duration::from_millis(500);
+
+// Once RCON is up, the game time says whether the main thread is still ticking. A slow
+// server falls behind but keeps ticking, while a stuck one stops altogether. This is well
+// under max-tick-time, so we get to stop the server before its own watchdog crashes it.
+// A stuck server falls behind straight away, so the grace below never gets to it first.
+const NO_TICK_TIMEOUT_DURATION: Duration = Duration::from_secs(30);
+
+// How far behind a server can be and still count as keeping up. One that's further behind
+// has something busy on its main thread, like the plugin shutting it down, so it's left
+// alone for as long as it keeps ticking.
+const LAGGING_TICKS_THRESHOLD: i64 = 100;
+
+// Before RCON is up, the log is the only sign of progress. World generation logs every
+// second or so, but the plugin's structure search can go quiet for a long while, so
+// silence only counts for anything this long, and never once the server is ticking.
+const NO_LOG_TIMEOUT_DURATION: Duration = Duration::from_secs(120);
+
+// A server that stops answering status pings altogether is in much worse shape
+const UNRESPONSIVE_TIMEOUT_DURATION: Duration = Duration::from_secs(15);
+
+// The plugin shuts the server down before it finishes starting. One that gets as far as
+// answering queries and then sits there keeping up with its ticks has been left running,
+// so give the shutdown a moment and then move on. Without RCON there are no ticks to go
+// on, so finishing starting is the only sign.
+const FINISHED_STARTING_GRACE_DURATION: Duration = Duration::from_secs(10);
+
+fn local_address(port: u32) -> SocketAddr {
+    SocketAddr::from(([127, 0, 0, 1], port as u16))
+}
+
+fn write_varint(buffer: &mut Vec<u8>, value: i32) {
+    let mut value = value as u32;
+    loop {
+        if value & !0x7F == 0 {
+            buffer.push(value as u8);
+            return;
+        }
+        buffer.push((value & 0x7F) as u8 | 0x80);
+        value >>= 7;
+    }
+}
+
+fn read_varint(stream: &mut TcpStream) -> Result<i32, String> {
+    let mut value: u32 = 0;
+
+    for position in 0..5 {
+        let mut byte = [0u8; 1];
+        stream
+            .read_exact(&mut byte)
+            .map_err(|e| format!("Unable to read status response: {}", e))?;
+
+        value |= ((byte[0] & 0x7F) as u32) << (7 * position);
+
+        if byte[0] & 0x80 == 0 {
+            return Ok(value as i32);
+        }
+    }
+
+    Err("Status response has an oversized VarInt".to_string())
+}
+
+fn framed_packet(body: &[u8]) -> Vec<u8> {
+    let mut packet = vec![];
+    write_varint(&mut packet, body.len() as i32);
+    packet.extend_from_slice(body);
+    packet
+}
+
+// Server List Ping, as used by the multiplayer screen. The server's network threads answer
+// this from the moment the port is bound, which is before world generation starts.
+// Returns how long the server took to answer.
+pub fn ping_status(port: u32) -> Result<Duration, String> {
+    let sent_at = Instant::now();
+
+    let mut stream = TcpStream::connect_timeout(&local_address(port), PROBE_TIMEOUT_DURATION)
+        .map_err(|e| format!("Unable to connect to port {}: {}", port, e))?;
+    stream
+        .set_read_timeout(Some(PROBE_TIMEOUT_DURATION))
+        .and_then(|_| stream.set_write_timeout(Some(PROBE_TIMEOUT_DURATION)))
+        .map_err(|e| format!("Unable to set up status ping: {}", e))?;
+
+    let host = b"127.0.0.1";
+
+    // Handshake: packet 0, protocol version (-1 for "just asking"), address, port, next state 1 (status)
+    let mut handshake = vec![];
+    write_varint(&mut handshake, 0x00);
+    write_varint(&mut handshake, -1);
+    write_varint(&mut handshake, host.len() as i32);
+    handshake.extend_from_slice(host);
+    handshake.extend_from_slice(&(port as u16).to_be_bytes());
+    write_varint(&mut handshake, 1);
+
+    let mut request = framed_packet(&handshake);
+    request.extend(framed_packet(&[0x00]));
+
+    stream
+        .write_all(&request)
+        .map_err(|e| format!("Unable to send status ping: {}", e))?;
+
+    let _length = read_varint(&mut stream)?;
+    let packet_id = read_varint(&mut stream)?;
+    if packet_id != 0x00 {
+        return Err(format!("Unexpected status response packet {}", packet_id));
+    }
+
+    let json_length = read_varint(&mut stream)?;
+    if !(0..=32767 * 4).contains(&json_length) {
+        return Err(format!("Status response has an impossible length of {}", json_length));
+    }
+
+    let mut json = vec![0u8; json_length as usize];
+    stream
+        .read_exact(&mut json)
+        .map_err(|e| format!("Unable to read status response: {}", e))?;
+
+    Ok(sent_at.elapsed())
+}
+
+// The UDP query protocol. Its listener only starts once the server is done starting up.
+pub fn query_server(port: u32) -> Result<(), String> {
+    let socket = UdpSocket::bind("127.0.0.1:0")
+        .map_err(|e| format!("Unable to open a socket for querying: {}", e))?;
+    socket
+        .set_read_timeout(Some(PROBE_TIMEOUT_DURATION))
+        .and_then(|_| socket.connect(local_address(port)))
+        .map_err(|e| format!("Unable to set up query: {}", e))?;
+
+    // Only the low four bits of each byte of the session id are used
+    let session_id: [u8; 4] = [0x05, 0x0A, 0x05, 0x0A];
+
+    // Handshake, which is answered with a challenge token
+    let mut handshake = vec![0xFE, 0xFD, 0x09];
+    handshake.extend_from_slice(&session_id);
+    socket
+        .send(&handshake)
+        .map_err(|e| format!("Unable to send query handshake: {}", e))?;
+
+    let mut response = [0u8; 1500];
+    let length = socket
+        .recv(&mut response)
+        .map_err(|e| format!("No answer to query handshake: {}", e))?;
+
+    if length < 6 || response[0] != 0x09 || response[1..5] != session_id {
+        return Err("Unexpected answer to query handshake".to_string());
+    }
+
+    let challenge_token = String::from_utf8_lossy(&response[5..length])
+        .trim_end_matches('\0')
+        .parse::<i32>()
+        .map_err(|e| format!("Query challenge token is malformed: {}", e))?;
+
+    // Basic stat request
+    let mut stat_request = vec![0xFE, 0xFD, 0x00];
+    stat_request.extend_from_slice(&session_id);
+    stat_request.extend_from_slice(&challenge_token.to_be_bytes());
+    socket
+        .send(&stat_request)
+        .map_err(|e| format!("Unable to send query: {}", e))?;
+
+    let length = socket
+        .recv(&mut response)
+        .map_err(|e| format!("No answer to query: {}", e))?;
+
+    if length < 5 || response[0] != 0x00 || response[1..5] != session_id {
+        return Err("Unexpected answer to query".to_string());
+    }
+
+    Ok(())
+}
+
+// Keeps an eye on a running server, deciding when it's stuck rather than just slow
+pub struct HangDetector {
+    port: u32,
+    log_path: String,
+    last_log_size: u64,
+    last_progress_at: Instant,
+    // Servers take a while to bind their port, so this stays None until the first answer
+    last_answered_at: Option<Instant>,
+    finished_starting_at: Option<Instant>,
+    // The server's game time when it finished starting, for working out how far behind it is
+    gametime_baseline: Option<(Instant, i64)>,
+    // The last game time seen, and when it was first seen
+    last_tick: Option<(Instant, i64)>,
+}
+
+impl HangDetector {
+    pub fn new(port: u32, server_dir: &str) -> HangDetector {
+        HangDetector {
+            port,
+            log_path: format!("{}/logs/latest.log", server_dir),
+            last_log_size: 0,
+            last_progress_at: Instant::now(),
+            last_answered_at: None,
+            finished_starting_at: None,
+            gametime_baseline: None,
+            last_tick: None,
+        }
+    }
+
+    fn query_gametime(rcon: Option<&mut RconClient>) -> Option<i64> {
+        rcon?
+            .command("time query gametime")
+            .ok()?
+            .rsplit(' ')
+            .next()?
+            .trim()
+            .parse::<i64>()
+            .ok()
+    }
+
+    // Keeps track of the game time, returning how many ticks the server has fallen behind
+    // the 20 per second it should be managing. A command that goes unanswered counts as
+    // no ticks at all, since RCON commands wait on the main thread.
+    fn ticks_behind(&mut self, rcon: Option<&mut RconClient>) -> Option<i64> {
+        let now = Instant::now();
+
+        if let Some(gametime) = HangDetector::query_gametime(rcon) {
+            if self.gametime_baseline.is_none() {
+                self.gametime_baseline = Some((now, gametime));
+            }
+            match self.last_tick {
+                Some((_, last_gametime)) if last_gametime == gametime => (),
+                _ => self.last_tick = Some((now, gametime)),
+            };
+        };
+
+        let (measured_at, baseline) = self.gametime_baseline?;
+        let (_, last_gametime) = self.last_tick?;
+        let expected_ticks = (now.duration_since(measured_at).as_secs_f64() * 20.0) as i64;
+        Some(expected_ticks - (last_gametime - baseline))
+    }
+
+    // Returns why the server should be given up on, if it should be
+    pub fn probe(&mut self, rcon: Option<&mut RconClient>) -> Option<String> {
+        let now = Instant::now();
+
+        let log_size = fs::metadata(&self.log_path).map(|metadata| metadata.len()).unwrap_or(0);
+        if log_size != self.last_log_size {
+            self.last_log_size = log_size;
+            self.last_progress_at = now;
+        }
+
+        if ping_status(self.port).is_ok() {
+            self.last_answered_at = Some(now);
+        }
+
+        if self.finished_starting_at.is_none() && query_server(self.port).is_ok() {
+            self.finished_starting_at = Some(now);
+        }
+
+        // Ticks are the best evidence either way, once there are any to go on
+        let ticks_behind = self.ticks_behind(rcon);
+
+        if let (Some(ticks_behind), Some((last_ticked_at, _))) = (ticks_behind, self.last_tick) {
+            if now.duration_since(last_ticked_at) >= NO_TICK_TIMEOUT_DURATION {
+                return Some(format!(
+                    "server hasn't ticked in {}s and is {} ticks behind",
+                    NO_TICK_TIMEOUT_DURATION.as_secs(),
+                    ticks_behind
+                ));
+            }
+        };
+
+        if let Some(finished_starting_at) = self.finished_starting_at {
+            if now.duration_since(finished_starting_at) >= FINISHED_STARTING_GRACE_DURATION {
+                match ticks_behind {
+                    // Still ticking, just slowly, so whatever is keeping it busy gets to finish
+                    Some(ticks_behind) if ticks_behind > LAGGING_TICKS_THRESHOLD => (),
+                    Some(ticks_behind) => {
+                        return Some(format!(
+                            "server finished starting but the plugin never shut it down, {} ticks behind",
+                            ticks_behind
+                        ));
+                    }
+                    None => {
+                        return Some(
+                            "server finished starting but the plugin never shut it down".to_string(),
+                        );
+                    }
+                };
+            }
+        };
+
+        if let Some(last_answered_at) = self.last_answered_at {
+            if now.duration_since(last_answered_at) >= UNRESPONSIVE_TIMEOUT_DURATION {
+                return Some(format!(
+                    "server hasn't answered a status ping in {}s",
+                    UNRESPONSIVE_TIMEOUT_DURATION.as_secs()
+                ));
+            }
+        };
+
+        if ticks_behind.is_none() && now.duration_since(self.last_progress_at) >= NO_LOG_TIMEOUT_DURATION {
+            return Some(format!(
+                "server hasn't logged anything in {}s",
+                NO_LOG_TIMEOUT_DURATION.as_secs()
+            ));
+        }
+
+        None
+    }
+}
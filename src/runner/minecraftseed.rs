@@ -0,0 +1,95 @@
+// Java's String.hashCode, which Minecraft uses to turn text seeds into numbers.
+// It works on UTF-16 code units, so the text has to be re-encoded first.
+pub fn java_string_hash(text: &str) -> i32 {
+    text.encode_utf16()
+        .fold(0i32, |hash, unit| hash.wrapping_mul(31).wrapping_add(unit as i32))
+}
+
+// The numeric seed Minecraft generates a world from, given what level-seed was set to.
+// None means the seed is empty, in which case Minecraft picks one at random.
+//
+// This follows 1.18 onwards: the seed is trimmed like Java's String.trim, used as-is if
+// Long.parseLong accepts it, and hashed otherwise. Long.parseLong also accepts non-ASCII
+// digits, which we don't, but nobody's handing those out as seeds.
+pub fn resolve_seed(level_seed: &str) -> Option<i64> {
+    let trimmed = level_seed.trim_matches(|c: char| c <= ' ');
+
+    if trimmed.is_empty() {
+        return None;
+    }
+
+    match trimmed.parse::<i64>() {
+        Ok(numeric_seed) => Some(numeric_seed),
+        Err(_) => Some(java_string_hash(trimmed) as i64),
+    }
+}
+
+// The plugin starts SpicyGardenData.txt with "Seed: <world.getSeed()>"
+pub fn reported_seed(result: &str) -> Option<i64> {
+    result
+        .lines()
+        .find_map(|line| line.strip_prefix("Seed: "))
+        .and_then(|seed| seed.trim().parse::<i64>().ok())
+}
+
+// The `seed` command answers with something like "Seed: [-4172144997902289642]"
+pub fn parse_seed_command(response: &str) -> Option<i64> {
+    let start = response.find('[')? + 1;
+    let end = start + response[start..].find(']')?;
+    response[start..end].trim().parse::<i64>().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn numeric_seeds_are_used_as_is() {
+        assert_eq!(resolve_seed("12345"), Some(12345));
+        assert_eq!(resolve_seed("-4172144997902289642"), Some(-4172144997902289642));
+        assert_eq!(resolve_seed("+42"), Some(42));
+        assert_eq!(resolve_seed("9223372036854775807"), Some(i64::MAX));
+        assert_eq!(resolve_seed("-9223372036854775808"), Some(i64::MIN));
+    }
+
+    #[test]
+    fn out_of_range_numbers_are_hashed() {
+        assert_eq!(resolve_seed("9223372036854775808"), Some(-1773151197));
+        assert_eq!(resolve_seed("-9223372036854775809"), Some(1304595159));
+    }
+
+    #[test]
+    fn surrounding_whitespace_is_trimmed() {
+        assert_eq!(resolve_seed("  12345\t"), Some(12345));
+        assert_eq!(resolve_seed("\n hello \r"), Some(99162322));
+        // Spaces inside the seed are part of it
+        assert_eq!(resolve_seed("12 34"), Some(46774464));
+    }
+
+    #[test]
+    fn empty_seeds_are_random() {
+        assert_eq!(resolve_seed(""), None);
+        assert_eq!(resolve_seed(" \t\n"), None);
+    }
+
+    #[test]
+    fn text_seeds_are_hashed_like_java() {
+        assert_eq!(resolve_seed("hello"), Some(99162322));
+        assert_eq!(resolve_seed("test"), Some(3556498));
+        assert_eq!(resolve_seed("Minecraft"), Some(-1595926131));
+        assert_eq!(resolve_seed("glacier 2"), Some(883625473));
+        // Outside the BMP, so hashed as a surrogate pair
+        assert_eq!(resolve_seed("😀"), Some(1772899));
+    }
+
+    #[test]
+    fn reported_seeds_are_read_from_results_and_commands() {
+        assert_eq!(reported_seed("Seed: -42\nStructure: village"), Some(-42));
+        assert_eq!(reported_seed("Structure: village"), None);
+        assert_eq!(
+            parse_seed_command("Seed: [-4172144997902289642]"),
+            Some(-4172144997902289642)
+        );
+        assert_eq!(parse_seed_command("Unknown command"), None);
+    }
+}
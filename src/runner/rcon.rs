@@ -0,0 +1,142 @@
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::time::Duration;
+
+// Packet types from the Source RCON protocol. Minecraft reuses 2 for both
+// running a command and answering a login.
+const PACKET_TYPE_RESPONSE: i32 = 0;
+const PACKET_TYPE_COMMAND: i32 = 2;
+const PACKET_TYPE_LOGIN: i32 = 3;
+
+// Anything Minecraft doesn't recognise gets an "Unknown request" reply, which
+// makes a handy marker for the end of a command's (possibly split up) response
+const PACKET_TYPE_END_MARKER: i32 = 100;
+
+// Minecraft drops anything longer than this
+const MAX_COMMAND_LENGTH: usize = 1446;
+
+struct Packet {
+    request_id: i32,
+    packet_type: i32,
+    body: String,
+}
+
+// A logged-in connection to a server's RCON port
+pub struct RconClient {
+    stream: TcpStream,
+    next_request_id: i32,
+}
+
+impl RconClient {
+    pub fn connect(port: u32, password: &str, timeout: Duration) -> Result<RconClient, String> {
+        let address = SocketAddr::from(([127, 0, 0, 1], port as u16));
+
+        let stream = TcpStream::connect_timeout(&address, timeout)
+            .map_err(|e| format!("Unable to connect to RCON on port {}: {}", port, e))?;
+        stream
+            .set_read_timeout(Some(timeout))
+            .and_then(|_| stream.set_write_timeout(Some(timeout)))
+            .map_err(|e| format!("Unable to set up RCON connection: {}", e))?;
+
+        let mut client = RconClient {
+            stream,
+            next_request_id: 1,
+        };
+
+        let login_id = client.send(PACKET_TYPE_LOGIN, password)?;
+        let response = client.receive()?;
+
+        // A failed login is answered with a request id of -1
+        if response.request_id != login_id {
+            return Err("RCON login was refused".to_string());
+        }
+
+        Ok(client)
+    }
+
+    fn send(&mut self, packet_type: i32, body: &str) -> Result<i32, String> {
+        let request_id = self.next_request_id;
+        self.next_request_id += 1;
+
+        // Length covers everything after itself: id, type, body and two null terminators
+        let length = (4 + 4 + body.len() + 2) as i32;
+
+        let mut packet = Vec::with_capacity(4 + length as usize);
+        packet.extend_from_slice(&length.to_le_bytes());
+        packet.extend_from_slice(&request_id.to_le_bytes());
+        packet.extend_from_slice(&packet_type.to_le_bytes());
+        packet.extend_from_slice(body.as_bytes());
+        packet.extend_from_slice(&[0, 0]);
+
+        self.stream
+            .write_all(&packet)
+            .map_err(|e| format!("Unable to send RCON packet: {}", e))?;
+
+        Ok(request_id)
+    }
+
+    fn receive(&mut self) -> Result<Packet, String> {
+        let mut length_bytes = [0u8; 4];
+        self.stream
+            .read_exact(&mut length_bytes)
+            .map_err(|e| format!("Unable to read RCON response: {}", e))?;
+
+        let length = i32::from_le_bytes(length_bytes);
+        if !(10..=4096 + 10).contains(&length) {
+            return Err(format!("RCON response has an impossible length of {}", length));
+        }
+
+        let mut packet = vec![0u8; length as usize];
+        self.stream
+            .read_exact(&mut packet)
+            .map_err(|e| format!("Unable to read RCON response: {}", e))?;
+
+        let request_id = i32::from_le_bytes([packet[0], packet[1], packet[2], packet[3]]);
+        let packet_type = i32::from_le_bytes([packet[4], packet[5], packet[6], packet[7]]);
+        let body = String::from_utf8_lossy(&packet[8..packet.len() - 2]).to_string();
+
+        Ok(Packet {
+            request_id,
+            packet_type,
+            body,
+        })
+    }
+
+    // Runs a console command and returns whatever it printed
+    pub fn command(&mut self, command: &str) -> Result<String, String> {
+        if command.len() > MAX_COMMAND_LENGTH {
+            return Err(format!("RCON command is longer than {} bytes", MAX_COMMAND_LENGTH));
+        }
+
+        let command_id = self.send(PACKET_TYPE_COMMAND, command)?;
+        let end_marker_id = self.send(PACKET_TYPE_END_MARKER, "")?;
+
+        let mut output = String::new();
+
+        loop {
+            let response = self.receive()?;
+
+            if response.request_id == end_marker_id {
+                return Ok(output);
+            }
+
+            if response.request_id == command_id && response.packet_type == PACKET_TYPE_RESPONSE {
+                output.push_str(&response.body);
+            }
+        }
+    }
+
+    // Whether the server is still answering commands
+    pub fn is_alive(&mut self) -> bool {
+        self.command("list").is_ok()
+    }
+}
+
+// A fresh password for each server, so nothing else on this machine can log in to it.
+// It comes straight from the OS, since HashMap's seeds were never meant to be secret.
+pub fn generate_password() -> Result<String, String> {
+    let mut bytes = [0u8; 32];
+    getrandom::getrandom(&mut bytes)
+        .map_err(|e| format!("Unable to generate an RCON password: {}", e))?;
+    Ok(hex::encode(bytes))
+}
@@ -1,13 +1,67 @@
-pub fn get_server_properties(runner_index: u32, seed: &String) -> String {
-    let server_port: u32 = 25565 + runner_index;
+use super::MAX_RUNNER_COUNT;
+
+// Java's Properties format treats backslashes, line breaks and leading spaces specially,
+// and older servers read it as Latin-1, so anything unusual gets escaped
+fn escape_property_value(value: &str) -> String {
+    let mut escaped = String::new();
+
+    for (index, c) in value.chars().enumerate() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            ' ' if index == 0 => escaped.push_str("\\ "),
+            c if c.is_ascii() && !c.is_ascii_control() => escaped.push(c),
+            c => {
+                let mut utf16 = [0u16; 2];
+                for unit in c.encode_utf16(&mut utf16) {
+                    escaped.push_str(&format!("\\u{:04x}", unit));
+                }
+            }
+        };
+    }
+
+    escaped
+}
+
+const SERVER_PORT_BASE: u32 = 25565;
+const RCON_PORT_BASE: u32 = 35565;
+
+// Each search gets a block of MAX_RUNNER_COUNT ports to itself, so searches running side by
+// side don't collide. Every slot's server ports fit below the first RCON port.
+pub const MAX_SEARCH_SLOTS: u32 = (RCON_PORT_BASE - SERVER_PORT_BASE) / MAX_RUNNER_COUNT;
+
+fn port_offset(search_slot: u32, runner_index: u32) -> u32 {
+    search_slot * MAX_RUNNER_COUNT + runner_index
+}
+
+// Also used for the query protocol, which is UDP and so doesn't clash
+pub fn server_port(search_slot: u32, runner_index: u32) -> u32 {
+    SERVER_PORT_BASE + port_offset(search_slot, runner_index)
+}
+
+pub fn rcon_port(search_slot: u32, runner_index: u32) -> u32 {
+    RCON_PORT_BASE + port_offset(search_slot, runner_index)
+}
+
+// server-ip keeps the game port, RCON and query on loopback, since nothing
+// off this machine has any business talking to a runner
+pub fn get_server_properties(
+    search_slot: u32,
+    runner_index: u32,
+    seed: &str,
+    rcon_password: &str,
+) -> String {
+    let server_port: u32 = server_port(search_slot, runner_index);
     format!(
         "#Minecraft server properties
 #Generated by SpicyGarden
 enable-jmx-monitoring=false
-rcon.port=
+rcon.port={}
 gamemode=survival
 enable-command-block=false
-enable-query=false
+enable-query=true
 level-name=world
 motd=SpicyGarden
 query.port={}
@@ -23,11 +77,11 @@ enable-status=true
 allow-flight=true
 broadcast-rcon-to-ops=true
 view-distance=4
-server-ip=
+server-ip=127.0.0.1
 resource-pack-prompt=
 allow-nether=false
 server-port={}
-enable-rcon=false
+enable-rcon=true
 sync-chunk-writes=true
 op-permission-level=4
 prevent-proxy-connections=false
@@ -35,7 +89,7 @@ hide-online-players=false
 resource-pack=
 entity-broadcast-range-percentage=100
 simulation-distance=4
-rcon.password=
+rcon.password={}
 player-idle-timeout=0
 debug=false
 force-gamemode=false
@@ -55,7 +109,37 @@ spawn-protection=4
 max-world-size=8000
 level-seed={}
 ",
-        server_port, server_port, seed
+        rcon_port(search_slot, runner_index),
+        server_port,
+        server_port,
+        rcon_password,
+        escape_property_value(seed)
     )
     .to_string()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn port_blocks_never_overlap() {
+        let last_slot = MAX_SEARCH_SLOTS - 1;
+        let last_runner = MAX_RUNNER_COUNT - 1;
+
+        for slot in 0..last_slot {
+            assert!(server_port(slot, last_runner) < server_port(slot + 1, 0));
+            assert!(rcon_port(slot, last_runner) < rcon_port(slot + 1, 0));
+        }
+
+        assert!(server_port(last_slot, last_runner) < rcon_port(0, 0));
+        assert!(rcon_port(last_slot, last_runner) <= u16::MAX as u32);
+    }
+
+    #[test]
+    fn property_values_are_escaped() {
+        assert_eq!(escape_property_value("plain seed"), "plain seed");
+        assert_eq!(escape_property_value(" a\\b\n"), "\\ a\\\\b\\n");
+        assert_eq!(escape_property_value("é😀"), "\\u00e9\\ud83d\\ude00");
+    }
+}
@@ -0,0 +1,197 @@
+use std::time::Instant;
+
+use iced::{Column, Length, Row, Text};
+
+use crate::runner::{RunnerEvent, RunnerPhase};
+use crate::Message;
+
+// How many of the latest events the GUI's log keeps around
+pub const RECENT_EVENT_COUNT: usize = 8;
+
+// How a runner's most recent seed turned out
+#[derive(Debug, Clone, PartialEq)]
+pub enum RunnerOutcome {
+    Completed,
+    Failed(String),
+    TimedOut,
+}
+
+// Everything the GUI knows about a single runner, kept up to date from runner events
+pub struct RunnerStatus {
+    pub runner_index: u32,
+    pub current_seed: Option<String>,
+    pub phase: Option<RunnerPhase>,
+    pub seed_started_at: Option<Instant>,
+    pub last_outcome: Option<RunnerOutcome>,
+    pub success_count: u32,
+    pub failure_count: u32,
+    pub timeout_count: u32,
+}
+
+impl RunnerStatus {
+    pub fn new(runner_index: u32) -> RunnerStatus {
+        RunnerStatus {
+            runner_index,
+            current_seed: None,
+            phase: None,
+            seed_started_at: None,
+            last_outcome: None,
+            success_count: 0,
+            failure_count: 0,
+            timeout_count: 0,
+        }
+    }
+
+    // Marks the runner as idle after it's done with its seed, one way or another
+    fn finish_seed(&mut self, outcome: RunnerOutcome) {
+        self.current_seed = None;
+        self.phase = None;
+        self.seed_started_at = None;
+        self.last_outcome = Some(outcome);
+    }
+}
+
+pub fn new_runner_statuses(runner_count: u32) -> Vec<RunnerStatus> {
+    (0..runner_count).map(RunnerStatus::new).collect()
+}
+
+pub fn update_runner_statuses(statuses: &mut [RunnerStatus], event: &RunnerEvent) {
+    let runner_index = match event {
+        RunnerEvent::RunnerStarted { runner_index, .. }
+        | RunnerEvent::PhaseChanged { runner_index, .. }
+        | RunnerEvent::SeedCompleted { runner_index, .. }
+        | RunnerEvent::SeedFailed { runner_index, .. }
+        | RunnerEvent::SeedTimedOut { runner_index, .. } => *runner_index,
+        _ => return,
+    };
+
+    let status = match statuses.get_mut(runner_index as usize) {
+        Some(status) => status,
+        None => return,
+    };
+
+    match event {
+        RunnerEvent::RunnerStarted { seed, .. } => {
+            status.current_seed = Some(seed.clone());
+            status.phase = None;
+            status.seed_started_at = Some(Instant::now());
+        }
+        RunnerEvent::PhaseChanged { phase, .. } => {
+            status.phase = Some(*phase);
+        }
+        RunnerEvent::SeedCompleted { .. } => {
+            status.success_count += 1;
+            status.finish_seed(RunnerOutcome::Completed);
+        }
+        RunnerEvent::SeedFailed { reason, .. } => {
+            status.failure_count += 1;
+            status.finish_seed(RunnerOutcome::Failed(reason.clone()));
+        }
+        RunnerEvent::SeedTimedOut { .. } => {
+            status.timeout_count += 1;
+            status.finish_seed(RunnerOutcome::TimedOut);
+        }
+        _ => (),
+    };
+}
+
+// A line for the GUI's log, for the events that the runner table doesn't already show
+pub fn describe_event(event: &RunnerEvent) -> Option<String> {
+    let description = match event {
+        RunnerEvent::SeedAssigned { seed } => format!("Seed {} assigned", seed),
+        RunnerEvent::SeedCompleted { runner_index, seed } => {
+            format!("Runner {} completed seed {}", runner_index, seed)
+        }
+        RunnerEvent::SeedFailed {
+            runner_index,
+            seed,
+            reason,
+        } => format!("Runner {} failed seed {}: {}", runner_index, seed, reason),
+        RunnerEvent::SeedTimedOut { runner_index, seed } => {
+            format!("Runner {} timed out on seed {}", runner_index, seed)
+        }
+        RunnerEvent::PreviewRendered { seed, .. } => format!("Rendered a preview of seed {}", seed),
+        RunnerEvent::SubmissionSucceeded { seed } => format!("Submitted seed {}", seed),
+        RunnerEvent::SubmissionFailed { seed, reason } => {
+            format!("Unable to submit seed {}: {}", seed, reason)
+        }
+        _ => return None,
+    };
+
+    Some(description)
+}
+
+// Newest first
+pub fn view_event_log<'a>(lines: &[String]) -> Column<'a, Message> {
+    lines.iter().fold(
+        Column::new().spacing(4).push(Text::new("Latest events")),
+        |column, line| column.push(Text::new(line.clone()).size(16)),
+    )
+}
+
+fn table_cell(content: String, width: u16) -> Text {
+    Text::new(content).size(16).width(Length::Units(width))
+}
+
+fn table_row<'a>(cells: Vec<(String, u16)>) -> Row<'a, Message> {
+    cells
+        .into_iter()
+        .fold(Row::new().spacing(8), |row, (content, width)| {
+            row.push(table_cell(content, width))
+        })
+}
+
+pub fn view_runner_table<'a>(statuses: &[RunnerStatus]) -> Column<'a, Message> {
+    let column_widths: [u16; 8] = [40, 160, 130, 70, 150, 40, 40, 40];
+
+    let header = ["#", "Seed", "Phase", "Elapsed", "Last outcome", "OK", "Fail", "T/O"];
+
+    let mut table = Column::new().spacing(4).push(table_row(
+        header
+            .iter()
+            .zip(column_widths.iter())
+            .map(|(title, width)| (title.to_string(), *width))
+            .collect(),
+    ));
+
+    for status in statuses {
+        let phase = match (&status.current_seed, status.phase) {
+            (Some(_), Some(phase)) => phase.to_string(),
+            (Some(_), None) => "Starting".to_string(),
+            (None, _) => "Idle".to_string(),
+        };
+
+        let elapsed = match status.seed_started_at {
+            Some(started_at) => format!("{}s", started_at.elapsed().as_secs()),
+            None => "-".to_string(),
+        };
+
+        let last_outcome = match &status.last_outcome {
+            Some(RunnerOutcome::Completed) => "Completed".to_string(),
+            Some(RunnerOutcome::Failed(reason)) => format!("Failed: {}", reason),
+            Some(RunnerOutcome::TimedOut) => "Timed out".to_string(),
+            None => "-".to_string(),
+        };
+
+        let values = [
+            status.runner_index.to_string(),
+            status.current_seed.clone().unwrap_or_else(|| "-".to_string()),
+            phase,
+            elapsed,
+            last_outcome,
+            status.success_count.to_string(),
+            status.failure_count.to_string(),
+            status.timeout_count.to_string(),
+        ];
+
+        table = table.push(table_row(
+            values
+                .iter()
+                .cloned()
+                .zip(column_widths.iter().cloned())
+                .collect(),
+        ));
+    }
+
+    table
+}
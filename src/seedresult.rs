@@ -0,0 +1,301 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::Serialize;
+
+// How far from spawn a biome sample can be and still count towards spawn's diversity
+const SPAWN_DIVERSITY_RADIUS: f64 = 256.0;
+
+#[derive(Serialize)]
+pub struct StructureSample {
+    // Names as the plugin writes them, e.g. "village" (or "VILLAGE" when not found)
+    pub name: String,
+    // None if the plugin didn't find one within its search distance
+    pub position: Option<(i64, i64, i64)>,
+}
+
+// The plugin samples at a fixed height, so only x and z are kept
+#[derive(Serialize)]
+pub struct BiomeSample {
+    pub x: i64,
+    pub z: i64,
+    pub biome: String,
+}
+
+// SpicyGardenData.txt, picked apart
+pub struct ParsedResult {
+    pub seed: Option<i64>,
+    pub structures: Vec<StructureSample>,
+    pub biomes: Vec<BiomeSample>,
+}
+
+// "(12,64,-30)" into its three numbers
+fn parse_position(text: &str) -> Option<(i64, i64, i64)> {
+    let mut coordinates = text
+        .trim_start_matches('(')
+        .trim_end_matches(')')
+        .split(',')
+        .map(|coordinate| coordinate.trim().parse::<i64>());
+
+    match (coordinates.next(), coordinates.next(), coordinates.next()) {
+        (Some(Ok(x)), Some(Ok(y)), Some(Ok(z))) => Some((x, y, z)),
+        _ => None,
+    }
+}
+
+// Lines look like "Seed: 123", "Structure: (x,y,z) name", "Structure: NOTFOUND NAME"
+// and "Biome: (x,y,z) NAME". Anything else is skipped.
+pub fn parse_result(result: &str) -> ParsedResult {
+    let mut parsed = ParsedResult {
+        seed: None,
+        structures: vec![],
+        biomes: vec![],
+    };
+
+    for line in result.lines() {
+        if let Some(seed) = line.strip_prefix("Seed: ") {
+            parsed.seed = seed.trim().parse::<i64>().ok();
+        } else if let Some(structure) = line.strip_prefix("Structure: ") {
+            if let Some((location, name)) = structure.split_once(' ') {
+                parsed.structures.push(StructureSample {
+                    name: name.trim().to_string(),
+                    position: parse_position(location),
+                });
+            }
+        } else if let Some(biome) = line.strip_prefix("Biome: ") {
+            if let Some((location, name)) = biome.split_once(' ') {
+                if let Some((x, _, z)) = parse_position(location) {
+                    parsed.biomes.push(BiomeSample {
+                        x,
+                        z,
+                        biome: name.trim().to_string(),
+                    });
+                }
+            }
+        }
+    }
+
+    parsed
+}
+
+// Distances are measured from spawn where the world put it, or from 0,0 (where the plugin
+// searches from) if that isn't known. Everything that measures distances goes through this,
+// so the summary, stored queries and rules all agree.
+pub fn distance_origin(spawn: Option<(i64, i64, i64)>) -> (i64, i64) {
+    spawn.map(|(x, _, z)| (x, z)).unwrap_or((0, 0))
+}
+
+fn horizontal_distance(from: (i64, i64), to: (i64, i64)) -> f64 {
+    let (dx, dz) = ((to.0 - from.0) as f64, (to.1 - from.1) as f64);
+    (dx * dx + dz * dz).sqrt()
+}
+
+// The structure called `name` nearest to `origin`, as its distance and x,z position.
+// Names are compared case-insensitively, since the plugin isn't consistent about them.
+pub fn nearest_structure(
+    structures: &[StructureSample],
+    name: &str,
+    origin: (i64, i64),
+) -> Option<(f64, (i64, i64))> {
+    structures
+        .iter()
+        .filter(|structure| structure.name.eq_ignore_ascii_case(name))
+        .filter_map(|structure| structure.position)
+        .map(|(x, _, z)| (horizontal_distance(origin, (x, z)), (x, z)))
+        .fold(None, |nearest: Option<(f64, (i64, i64))>, candidate| match nearest {
+            Some(nearest) if nearest.0 <= candidate.0 => Some(nearest),
+            _ => Some(candidate),
+        })
+}
+
+// The share of the biome samples in `biome`, 0 to 1, comparing names case-insensitively
+pub fn biome_share(biomes: &[BiomeSample], biome: &str) -> f64 {
+    if biomes.is_empty() {
+        return 0.0;
+    }
+
+    let count = biomes
+        .iter()
+        .filter(|sample| sample.biome.eq_ignore_ascii_case(biome))
+        .count();
+    count as f64 / biomes.len() as f64
+}
+
+// A biome condition holds when the biome shows up in the samples at all and makes up at
+// least `min_share` of them, so a minimum of 0 means "anywhere in the samples"
+pub fn meets_biome_share(share: f64, min_share: f64) -> bool {
+    share > 0.0 && share >= min_share
+}
+
+// A compact stand-in for the hundreds of biome samples in a raw result
+#[derive(Serialize)]
+pub struct ResultSummary {
+    pub seed: Option<i64>,
+    pub biome_sample_count: usize,
+    pub distinct_biome_count: usize,
+    // Share of samples in each biome, 0 to 1, keyed by lowercase name like the structures
+    pub biome_shares: BTreeMap<String, f64>,
+    // Horizontal distance from spawn (or 0,0 if it isn't known) to each structure type,
+    // or None if there wasn't one in range
+    pub nearest_structures: BTreeMap<String, Option<f64>>,
+    // Shannon diversity index of the biome samples near spawn
+    pub spawn_biome_diversity: f64,
+}
+
+// Biome names are compared case-insensitively, like everywhere else
+fn shannon_index<'a>(biomes: impl Iterator<Item = &'a str>) -> f64 {
+    let mut counts: BTreeMap<String, usize> = BTreeMap::new();
+    for biome in biomes {
+        *counts.entry(biome.to_lowercase()).or_insert(0) += 1;
+    }
+
+    let total: usize = counts.values().sum();
+
+    counts
+        .values()
+        .map(|count| {
+            let share = *count as f64 / total as f64;
+            -share * share.ln()
+        })
+        .sum()
+}
+
+// Spawn is where the world put it if we know, and the origin otherwise
+pub fn summarize_result(parsed: &ParsedResult, spawn: Option<(i64, i64, i64)>) -> ResultSummary {
+    let origin = distance_origin(spawn);
+
+    let biome_shares: BTreeMap<String, f64> = parsed
+        .biomes
+        .iter()
+        .map(|sample| sample.biome.to_lowercase())
+        .collect::<BTreeSet<String>>()
+        .into_iter()
+        .map(|biome| {
+            let share = biome_share(&parsed.biomes, &biome);
+            (biome, share)
+        })
+        .collect();
+
+    let nearest_structures = parsed
+        .structures
+        .iter()
+        .map(|structure| structure.name.to_lowercase())
+        .collect::<BTreeSet<String>>()
+        .into_iter()
+        .map(|name| {
+            let distance = nearest_structure(&parsed.structures, &name, origin)
+                .map(|(distance, _)| distance);
+            (name, distance)
+        })
+        .collect();
+
+    let spawn_biomes = parsed
+        .biomes
+        .iter()
+        .filter(|sample| horizontal_distance(origin, (sample.x, sample.z)) <= SPAWN_DIVERSITY_RADIUS)
+        .map(|sample| sample.biome.as_str());
+
+    ResultSummary {
+        seed: parsed.seed,
+        biome_sample_count: parsed.biomes.len(),
+        distinct_biome_count: biome_shares.len(),
+        biome_shares,
+        nearest_structures,
+        spawn_biome_diversity: shannon_index(spawn_biomes),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RESULT: &str = "Seed: -42
+Structure: (100,64,0) village
+Structure: (-300,70,400) Village
+Structure: NOTFOUND MANSION
+Biome: (0,64,0) JUNGLE
+Biome: (16,64,0) jungle
+Biome: (32,64,0) Plains
+Biome: (5000,64,0) ocean
+Some other line
+";
+
+    #[test]
+    fn results_are_parsed() {
+        let parsed = parse_result(RESULT);
+        assert_eq!(parsed.seed, Some(-42));
+        assert_eq!(parsed.structures.len(), 3);
+        assert_eq!(parsed.structures[0].position, Some((100, 64, 0)));
+        assert_eq!(parsed.biomes.len(), 4);
+        assert_eq!(parsed.biomes[3].x, 5000);
+        assert_eq!(parsed.biomes[3].biome, "ocean");
+    }
+
+    #[test]
+    fn notfound_structures_have_no_position() {
+        let parsed = parse_result(RESULT);
+        assert_eq!(parsed.structures[2].name, "MANSION");
+        assert_eq!(parsed.structures[2].position, None);
+
+        assert_eq!(nearest_structure(&parsed.structures, "mansion", (0, 0)), None);
+
+        let summary = summarize_result(&parsed, None);
+        assert_eq!(summary.nearest_structures.get("mansion"), Some(&None));
+    }
+
+    #[test]
+    fn names_are_case_insensitive() {
+        let parsed = parse_result(RESULT);
+
+        assert_eq!(
+            nearest_structure(&parsed.structures, "VILLAGE", (0, 0)),
+            Some((100.0, (100, 0)))
+        );
+        assert_eq!(biome_share(&parsed.biomes, "Jungle"), 0.5);
+
+        let summary = summarize_result(&parsed, None);
+        assert_eq!(
+            summary.biome_shares.keys().collect::<Vec<&String>>(),
+            vec!["jungle", "ocean", "plains"]
+        );
+        assert_eq!(summary.distinct_biome_count, 3);
+        assert_eq!(
+            summary.nearest_structures.keys().collect::<Vec<&String>>(),
+            vec!["mansion", "village"]
+        );
+    }
+
+    #[test]
+    fn distances_are_measured_from_spawn() {
+        let parsed = parse_result(RESULT);
+
+        // Without a spawn, the origin is where the plugin searches from
+        let summary = summarize_result(&parsed, None);
+        assert_eq!(summary.nearest_structures.get("village"), Some(&Some(100.0)));
+
+        // The village at -300,400 is nearer to this spawn than the one at 100,0
+        let summary = summarize_result(&parsed, Some((-300, 70, 100)));
+        assert_eq!(summary.nearest_structures.get("village"), Some(&Some(300.0)));
+
+        // Only the samples near spawn count towards its diversity
+        let summary = summarize_result(&parsed, Some((5000, 64, 0)));
+        assert_eq!(summary.spawn_biome_diversity, 0.0);
+    }
+
+    #[test]
+    fn biome_shares_need_the_biome_to_be_present() {
+        assert!(meets_biome_share(0.25, 0.0));
+        assert!(meets_biome_share(0.25, 0.25));
+        assert!(!meets_biome_share(0.0, 0.0));
+        assert!(!meets_biome_share(0.25, 0.5));
+        assert_eq!(biome_share(&[], "jungle"), 0.0);
+    }
+
+    #[test]
+    fn shannon_index_of_known_mixes() {
+        assert_eq!(shannon_index(vec!["plains"; 5].into_iter()), 0.0);
+        assert_eq!(shannon_index(std::iter::empty()), 0.0);
+
+        let two_equal = shannon_index(vec!["plains", "jungle", "JUNGLE", "plains"].into_iter());
+        assert!((two_equal - std::f64::consts::LN_2).abs() < 1e-12);
+    }
+}
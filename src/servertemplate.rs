@@ -0,0 +1,404 @@
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Component, Path};
+
+use reqwest::blocking::Client;
+use reqwest::StatusCode;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+use crate::gatherserver::GatherServer;
+
+pub const SERVER_TEMPLATE_DIR: &str = "server_template";
+
+// Holds one template per Minecraft version, e.g. server_templates/1.19.2
+pub const SERVER_TEMPLATES_DIR: &str = "server_templates";
+
+// Optional settings inside a template, for versions that need something other than the defaults
+const TEMPLATE_SETTINGS_FILE: &str = "template.toml";
+
+// If this exists, it's used instead of asking the gather server for a manifest
+pub const TEMPLATE_MANIFEST_PATH: &str = "template_manifest.toml";
+
+// Lists every file a server template needs, and exactly what each one should contain
+#[derive(Deserialize)]
+pub struct TemplateManifest {
+    pub files: Vec<ManifestFile>,
+}
+
+#[derive(Deserialize)]
+pub struct ManifestFile {
+    // Relative to the template, e.g. "plugins/SpicyGarden.jar"
+    pub path: String,
+    // Which of server_templates the file belongs in. Files without one go in server_template.
+    #[serde(default)]
+    pub template: Option<String>,
+    pub sha256: String,
+    // Where to download the file from. Files without one have to be put in place by hand.
+    #[serde(default)]
+    pub url: Option<String>,
+}
+
+// Identifies exactly which server and plugin a result was generated with,
+// so that results from different Minecraft versions never get mixed up
+#[derive(Debug, Clone)]
+pub struct TemplateFingerprint {
+    pub minecraft_version: Option<String>,
+    pub plugin_version: Option<String>,
+    pub server_jar_sha256: String,
+    pub plugin_sha256: String,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct TemplateSettings {
+    // The server jar, relative to the template
+    jar: Option<String>,
+    // The java executable to run it with, for versions that need an older or newer runtime
+    java: Option<String>,
+}
+
+// A directory runners copy to get a server for one Minecraft version
+#[derive(Debug, Clone)]
+pub struct ServerTemplate {
+    // The Minecraft version seeds ask for to get this template, or None for server_template
+    pub name: Option<String>,
+    pub dir: String,
+    pub jar: String,
+    pub java: String,
+}
+
+impl ServerTemplate {
+    fn load(name: Option<String>, dir: String) -> ServerTemplate {
+        let settings_path = format!("{}/{}", dir, TEMPLATE_SETTINGS_FILE);
+
+        let settings = match fs::read(&settings_path) {
+            Ok(toml_slice) => toml::from_slice::<TemplateSettings>(&toml_slice).unwrap_or_else(|e| {
+                println!("ERROR: Failed to parse {}: {:?}", settings_path, e);
+                TemplateSettings::default()
+            }),
+            Err(_) => TemplateSettings::default(),
+        };
+
+        ServerTemplate {
+            name,
+            dir,
+            jar: settings.jar.unwrap_or_else(|| "server.jar".to_string()),
+            java: settings.java.unwrap_or_else(|| "java".to_string()),
+        }
+    }
+
+    pub fn display_name(&self) -> &str {
+        self.name.as_deref().unwrap_or("default")
+    }
+
+    pub fn jar_path(&self) -> String {
+        format!("{}/{}", self.dir, self.jar)
+    }
+
+    pub fn plugin_path(&self) -> String {
+        format!("{}/plugins/SpicyGarden.jar", self.dir)
+    }
+}
+
+// server_template comes first if there is one, followed by server_templates/* by name
+pub fn find_templates() -> Vec<ServerTemplate> {
+    let mut templates = vec![];
+
+    if Path::new(SERVER_TEMPLATE_DIR).is_dir() {
+        templates.push(ServerTemplate::load(None, SERVER_TEMPLATE_DIR.to_string()));
+    }
+
+    let mut names: Vec<String> = match fs::read_dir(SERVER_TEMPLATES_DIR) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.path().is_dir())
+            .filter_map(|entry| entry.file_name().into_string().ok())
+            .collect(),
+        Err(_) => vec![],
+    };
+    names.sort();
+
+    for name in names {
+        let dir = format!("{}/{}", SERVER_TEMPLATES_DIR, name);
+        templates.push(ServerTemplate::load(Some(name), dir));
+    }
+
+    templates
+}
+
+// A template that's been fingerprinted and is ready for runners to use
+#[derive(Debug, Clone)]
+pub struct ReadyTemplate {
+    pub template: ServerTemplate,
+    pub fingerprint: TemplateFingerprint,
+}
+
+pub fn prepare_templates() -> Result<Vec<ReadyTemplate>, String> {
+    let templates = find_templates();
+
+    if templates.is_empty() {
+        return Err(format!(
+            "No server templates found in {} or {}",
+            SERVER_TEMPLATE_DIR, SERVER_TEMPLATES_DIR
+        ));
+    }
+
+    templates
+        .into_iter()
+        .map(|template| {
+            Ok(ReadyTemplate {
+                fingerprint: fingerprint_template(&template)?,
+                template,
+            })
+        })
+        .collect()
+}
+
+// Seeds that don't ask for a version get server_template (or whatever there is).
+// Seeds that do get the template named after it, or any template whose jar says it's that version.
+pub fn select_template<'a>(
+    templates: &'a [ReadyTemplate],
+    minecraft_version: Option<&str>,
+) -> Option<&'a ReadyTemplate> {
+    match minecraft_version {
+        None => templates
+            .iter()
+            .find(|ready| ready.template.name.is_none())
+            .or_else(|| templates.first()),
+        Some(version) => templates
+            .iter()
+            .find(|ready| ready.template.name.as_deref() == Some(version))
+            .or_else(|| {
+                templates
+                    .iter()
+                    .find(|ready| ready.fingerprint.minecraft_version.as_deref() == Some(version))
+            }),
+    }
+}
+
+// Hex-encoded SHA-256 of a whole file
+pub fn sha256_file(path: &str) -> Result<String, String> {
+    let mut file = fs::File::open(path).map_err(|e| format!("Unable to open {}: {}", path, e))?;
+
+    let mut hasher = Sha256::new();
+    io::copy(&mut file, &mut hasher).map_err(|e| format!("Unable to read {}: {}", path, e))?;
+
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+fn read_jar_entry(jar_path: &str, entry_name: &str) -> Option<String> {
+    let jar_file = fs::File::open(jar_path).ok()?;
+    let mut archive = zip::ZipArchive::new(jar_file).ok()?;
+    let mut entry = archive.by_name(entry_name).ok()?;
+
+    let mut contents = String::new();
+    entry.read_to_string(&mut contents).ok()?;
+    Some(contents)
+}
+
+#[derive(Deserialize)]
+struct VersionJson {
+    id: String,
+}
+
+// Vanilla jars (and the ones built from them) carry a version.json. Failing that,
+// Spigot puts something like "3576-Spigot-ab1234-cd5678 (MC: 1.19.2)" in the manifest.
+fn read_minecraft_version(server_jar_path: &str) -> Option<String> {
+    if let Some(version_json) = read_jar_entry(server_jar_path, "version.json") {
+        if let Ok(version) = serde_json::from_str::<VersionJson>(&version_json) {
+            return Some(version.id);
+        }
+    }
+
+    let manifest = read_jar_entry(server_jar_path, "META-INF/MANIFEST.MF")?;
+    let implementation_version = manifest
+        .lines()
+        .find_map(|line| line.strip_prefix("Implementation-Version:"))?;
+
+    let minecraft_version = implementation_version.split("(MC: ").nth(1)?;
+    Some(minecraft_version.trim_end_matches(|c: char| c == ')' || c.is_whitespace()).to_string())
+}
+
+fn read_plugin_version(plugin_jar_path: &str) -> Option<String> {
+    let plugin_yml = read_jar_entry(plugin_jar_path, "plugin.yml")?;
+
+    plugin_yml
+        .lines()
+        .find_map(|line| line.strip_prefix("version:"))
+        .map(|version| version.trim().to_string())
+}
+
+// Some server jars don't say which version they are anywhere we can read without
+// running them, but every one of them announces it in the log while starting up
+pub fn read_log_minecraft_version(server_dir: &str) -> Option<String> {
+    let log = fs::read_to_string(format!("{}/logs/latest.log", server_dir)).ok()?;
+
+    log.lines().find_map(|line| {
+        let version = line.split("Starting minecraft server version ").nth(1)?;
+        Some(version.trim().to_string())
+    })
+}
+
+pub fn fingerprint_template(template: &ServerTemplate) -> Result<TemplateFingerprint, String> {
+    let server_jar_path = template.jar_path();
+    let plugin_jar_path = template.plugin_path();
+
+    Ok(TemplateFingerprint {
+        minecraft_version: read_minecraft_version(&server_jar_path),
+        plugin_version: read_plugin_version(&plugin_jar_path),
+        server_jar_sha256: sha256_file(&server_jar_path)?,
+        plugin_sha256: sha256_file(&plugin_jar_path)?,
+    })
+}
+
+// Local manifests win, so a template can be pinned without the gather server's help.
+// Returns None if there's no manifest anywhere, in which case the template is up to the user.
+fn load_template_manifest(gather_server: &mut GatherServer) -> Result<Option<TemplateManifest>, String> {
+    if Path::new(TEMPLATE_MANIFEST_PATH).exists() {
+        let toml_slice = fs::read(TEMPLATE_MANIFEST_PATH)
+            .map_err(|e| format!("Unable to read {}: {}", TEMPLATE_MANIFEST_PATH, e))?;
+
+        return toml::from_slice::<TemplateManifest>(&toml_slice)
+            .map(Some)
+            .map_err(|e| format!("Failed to parse {}: {}", TEMPLATE_MANIFEST_PATH, e));
+    }
+
+    let response = gather_server
+        .get("template_manifest", &[])
+        .and_then(|request| request.send())
+        .map_err(|e| format!("Unable to fetch template manifest: {}", e))?;
+
+    match response.status() {
+        StatusCode::OK => response
+            .json::<TemplateManifest>()
+            .map(Some)
+            .map_err(|e| format!("Unable to understand gather server's template manifest: {}", e)),
+        // This gather server leaves setting up the template to its users
+        StatusCode::NOT_FOUND => Ok(None),
+        status => Err(format!(
+            "Gather server responded with {} when asked for a template manifest",
+            status
+        )),
+    }
+}
+
+// Manifests come from elsewhere, so make sure they can't write outside the template
+fn is_safe_template_path(path: &str) -> bool {
+    Path::new(path)
+        .components()
+        .all(|component| matches!(component, Component::Normal(_)))
+}
+
+fn file_matches(path: &str, sha256: &str) -> bool {
+    match sha256_file(path) {
+        Ok(actual) => actual.eq_ignore_ascii_case(sha256),
+        Err(_) => false,
+    }
+}
+
+// Downloads next to the destination first, so a half-finished or tampered download
+// never ends up in the template
+fn download_file(http_client: &Client, url: &str, destination: &str, sha256: &str) -> Result<(), String> {
+    if let Some(parent) = Path::new(destination).parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| format!("Unable to create {}: {}", parent.display(), e))?;
+    };
+
+    let download_path = format!("{}.download", destination);
+
+    let mut response = http_client
+        .get(url)
+        .send()
+        .map_err(|e| format!("Unable to download {}: {}", url, e))?;
+
+    if response.status() != StatusCode::OK {
+        return Err(format!("Downloading {} failed: {}", url, response.status()));
+    }
+
+    let mut download_file = fs::File::create(&download_path)
+        .map_err(|e| format!("Unable to create {}: {}", download_path, e))?;
+
+    if let Err(e) = io::copy(&mut response, &mut download_file) {
+        _ = fs::remove_file(&download_path);
+        return Err(format!("Unable to download {}: {}", url, e));
+    };
+
+    if !file_matches(&download_path, sha256) {
+        _ = fs::remove_file(&download_path);
+        return Err(format!(
+            "Downloaded {} doesn't match the manifest's SHA-256, refusing to use it",
+            url
+        ));
+    }
+
+    fs::rename(&download_path, destination)
+        .map_err(|e| format!("Unable to move {} into place: {}", destination, e))
+}
+
+// Brings server_template in line with the manifest, downloading anything missing or
+// different. Refuses to go on if any file can't be made to match.
+pub fn sync_template(gather_server: &mut GatherServer) -> Result<String, String> {
+    let manifest = match load_template_manifest(gather_server)? {
+        Some(manifest) => manifest,
+        None => return Ok("No template manifest available, using server templates as-is".to_string()),
+    };
+
+    // Server jars are big, and the default timeout doesn't leave room for slow connections
+    let http_client = Client::builder()
+        .timeout(None)
+        .build()
+        .map_err(|e| format!("Unable to set up downloads: {}", e))?;
+
+    let mut downloaded_count = 0;
+
+    for file in &manifest.files {
+        if !is_safe_template_path(&file.path) {
+            return Err(format!(
+                "Template manifest lists {:?}, which is outside its template",
+                file.path
+            ));
+        }
+
+        let template_dir = match &file.template {
+            None => SERVER_TEMPLATE_DIR.to_string(),
+            Some(name) if is_safe_template_path(name) && !name.contains('/') => {
+                format!("{}/{}", SERVER_TEMPLATES_DIR, name)
+            }
+            Some(name) => {
+                return Err(format!("Template manifest names an invalid template {:?}", name));
+            }
+        };
+
+        let destination = format!("{}/{}", template_dir, file.path);
+
+        if file_matches(&destination, &file.sha256) {
+            continue;
+        }
+
+        match &file.url {
+            Some(url) => {
+                println!("Downloading {} into {}", url, destination);
+                download_file(&http_client, url, &destination, &file.sha256)?;
+                downloaded_count += 1;
+            }
+            None => {
+                return Err(format!(
+                    "{} is missing or doesn't match the template manifest",
+                    destination
+                ));
+            }
+        };
+    }
+
+    Ok(format!(
+        "Server templates verified ({} files, {} downloaded)",
+        manifest.files.len(),
+        downloaded_count
+    ))
+}
+
+// Async wrapper function so the GUI doesn't freeze up while downloading
+pub async fn sync_template_async_wrapper(address: String, client_key: String) -> Result<String, String> {
+    sync_template(&mut GatherServer::new(&address, &client_key))
+}
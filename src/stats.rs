@@ -0,0 +1,239 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::gatherserver::GatherServer;
+use crate::runner::RunnerEvent;
+
+pub const STATS_FILE_PATH: &str = "stats.toml";
+
+// Stats are kept under a fingerprint of the client key rather than the key itself,
+// since stats.toml gets shared and printed without a second thought
+const FINGERPRINT_PREFIX: &str = "sha256:";
+
+pub fn key_fingerprint(client_key: &str) -> String {
+    let digest = Sha256::digest(client_key.as_bytes());
+    format!("{}{}", FINGERPRINT_PREFIX, hex::encode(&digest[..8]))
+}
+
+// Everything one client key has contributed from this machine
+#[derive(Serialize, Deserialize, Default, Clone)]
+#[serde(default)]
+pub struct ClientStats {
+    pub seeds_searched: u64,
+    pub runtime_seconds: f64,
+    pub failures: u64,
+    pub timeouts: u64,
+    // Seeds searched per UTC day, keyed like "2022-10-31"
+    pub daily_seeds: BTreeMap<String, u64>,
+}
+
+impl ClientStats {
+    fn merge(&mut self, other: ClientStats) {
+        self.seeds_searched += other.seeds_searched;
+        self.runtime_seconds += other.runtime_seconds;
+        self.failures += other.failures;
+        self.timeouts += other.timeouts;
+        for (day, seed_count) in other.daily_seeds {
+            *self.daily_seeds.entry(day).or_insert(0) += seed_count;
+        }
+    }
+
+    pub fn seeds_searched_today(&self) -> u64 {
+        *self.daily_seeds.get(&today()).unwrap_or(&0)
+    }
+
+    pub fn summary(&self) -> String {
+        format!(
+            "{} seeds ({} today), {:.1} hours, {} failures, {} timeouts",
+            self.seeds_searched,
+            self.seeds_searched_today(),
+            self.runtime_seconds / (60.0 * 60.0),
+            self.failures,
+            self.timeouts
+        )
+    }
+}
+
+// Lifetime statistics across every session, kept in stats.toml
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct LifetimeStats {
+    pub clients: BTreeMap<String, ClientStats>,
+}
+
+impl LifetimeStats {
+    // Loads stats.toml, starting from nothing if it doesn't exist yet
+    pub fn load() -> LifetimeStats {
+        let mut stats = match fs::read(STATS_FILE_PATH) {
+            Ok(toml_slice) => match toml::from_slice::<LifetimeStats>(&toml_slice) {
+                Ok(stats) => stats,
+                Err(e) => {
+                    println!("ERROR: Failed to parse {}: {:?}", STATS_FILE_PATH, e);
+                    LifetimeStats::default()
+                }
+            },
+            Err(_) => LifetimeStats::default(),
+        };
+
+        if stats.fingerprint_keys() {
+            stats.save();
+        }
+
+        stats
+    }
+
+    // Older versions kept stats under the raw client key. Those entries are moved
+    // under the key's fingerprint, merging with anything already there.
+    // Returns true if anything was moved.
+    fn fingerprint_keys(&mut self) -> bool {
+        let raw_keys: Vec<String> = self
+            .clients
+            .keys()
+            .filter(|key| !key.starts_with(FINGERPRINT_PREFIX))
+            .cloned()
+            .collect();
+
+        for raw_key in &raw_keys {
+            if let Some(client) = self.clients.remove(raw_key) {
+                self.clients
+                    .entry(key_fingerprint(raw_key))
+                    .or_default()
+                    .merge(client);
+            };
+        }
+
+        !raw_keys.is_empty()
+    }
+
+    pub fn save(&self) {
+        let toml_text = match toml::to_string(self) {
+            Ok(text) => text,
+            Err(e) => {
+                println!("ERROR: Failed to serialize lifetime stats: {:?}", e);
+                return;
+            }
+        };
+
+        if let Err(e) = fs::write(STATS_FILE_PATH, toml_text) {
+            println!("ERROR: Unable to write {}: {:?}", STATS_FILE_PATH, e);
+        };
+    }
+
+    pub fn client(&self, client_key: &str) -> ClientStats {
+        self.clients
+            .get(&key_fingerprint(client_key))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    fn client_mut(&mut self, client_key: &str) -> &mut ClientStats {
+        self.clients.entry(key_fingerprint(client_key)).or_default()
+    }
+
+    pub fn add_runtime(&mut self, client_key: &str, runtime: Duration) {
+        self.client_mut(client_key).runtime_seconds += runtime.as_secs_f64();
+    }
+
+    // Returns true if the event changed anything worth saving
+    pub fn record_event(&mut self, client_key: &str, event: &RunnerEvent) -> bool {
+        let client = self.client_mut(client_key);
+
+        match event {
+            RunnerEvent::SubmissionSucceeded { .. } => {
+                client.seeds_searched += 1;
+                *client.daily_seeds.entry(today()).or_insert(0) += 1;
+            }
+            RunnerEvent::SeedFailed { .. } => client.failures += 1,
+            RunnerEvent::SeedTimedOut { .. } => client.timeouts += 1,
+            _ => return false,
+        };
+
+        true
+    }
+}
+
+// What the gather server has on record for a client key
+#[derive(Deserialize)]
+pub struct ServerStats {
+    pub seeds_searched: u64,
+}
+
+pub fn fetch_server_stats(server_address: &str, client_key: &str) -> Result<ServerStats, String> {
+    let response = GatherServer::new(server_address, client_key)
+        .get("my_stats", &[])
+        .and_then(|request| request.send())
+        .map_err(|e| format!("Unable to contact gather server: {}", e))?;
+
+    if response.status() != 200 {
+        return Err(format!("Gather server responded with {}", response.status()));
+    }
+
+    response
+        .json::<ServerStats>()
+        .map_err(|e| format!("Unable to understand gather server's stats: {}", e))
+}
+
+// Today's date in UTC, formatted like "2022-10-31"
+fn today() -> String {
+    let days_since_epoch = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs() as i64
+        / (60 * 60 * 24);
+
+    // Howard Hinnant's civil_from_days, so we don't need a whole date crate for this
+    let z = days_since_epoch + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z - era * 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+// Prints lifetime stats for the `stats` command, optionally comparing them with the gather server.
+// Only the configured client key can be reconciled, since the others are only known by fingerprint.
+pub fn print_stats(server_address: &str, client_key: &str, reconcile: bool) {
+    let stats = LifetimeStats::load();
+
+    if stats.clients.is_empty() {
+        println!("No seeds have been searched on this machine yet.");
+        return;
+    }
+
+    let configured_fingerprint = key_fingerprint(client_key);
+
+    for (fingerprint, client) in &stats.clients {
+        let is_configured = *fingerprint == configured_fingerprint;
+        println!(
+            "{}{}: {}",
+            fingerprint,
+            if is_configured { " (configured key)" } else { "" },
+            client.summary()
+        );
+
+        for (day, seed_count) in &client.daily_seeds {
+            println!("    {}: {} seeds", day, seed_count);
+        }
+
+        if reconcile && is_configured {
+            match fetch_server_stats(server_address, client_key) {
+                Ok(server_stats) => {
+                    println!(
+                        "    Gather server has {} seeds on record ({} locally)",
+                        server_stats.seeds_searched, client.seeds_searched
+                    );
+                }
+                Err(e) => println!("    Unable to reconcile with gather server: {}", e),
+            }
+        }
+    }
+}
@@ -0,0 +1,124 @@
+use std::collections::HashMap;
+
+use crate::anvil::{self, RegionReader};
+
+// How far around spawn to look, in chunks. Spawn chunks are always fully generated,
+// and the plugin's sampling makes sure plenty more are too.
+const SPAWN_RADIUS_CHUNKS: i32 = 8;
+
+// Terrain features worked out from the chunks the server generated around spawn
+#[derive(Debug, Clone)]
+pub struct TerrainSummary {
+    pub column_count: usize,
+    pub min_height: i64,
+    pub max_height: i64,
+    pub mean_height: f64,
+    pub height_stddev: f64,
+    // Share of columns whose surface is in an ocean biome, if biomes could be read
+    pub ocean_coverage: Option<f64>,
+    // Share of columns with each surface biome, biggest first
+    pub surface_biomes: Vec<(String, f64)>,
+}
+
+impl TerrainSummary {
+    // e.g. "columns=73984; height_min=-12; height_max=143; height_mean=71.3; height_stddev=18.2; ocean=0.314"
+    pub fn header_value(&self) -> String {
+        let mut value = format!(
+            "columns={}; height_min={}; height_max={}; height_mean={:.1}; height_stddev={:.1}",
+            self.column_count, self.min_height, self.max_height, self.mean_height, self.height_stddev
+        );
+
+        if let Some(ocean_coverage) = self.ocean_coverage {
+            value.push_str(&format!("; ocean={:.3}", ocean_coverage));
+        }
+
+        value
+    }
+
+    // e.g. "minecraft:plains=0.412, minecraft:forest=0.201"
+    pub fn surface_biomes_header_value(&self) -> String {
+        self.surface_biomes
+            .iter()
+            .map(|(biome, share)| format!("{}={:.3}", biome, share))
+            .collect::<Vec<String>>()
+            .join(", ")
+    }
+}
+
+pub fn summarize_terrain(world_dir: &str, spawn: (i64, i64, i64)) -> Result<TerrainSummary, String> {
+    let mut region_reader = RegionReader::new(world_dir);
+
+    let spawn_chunk_x = anvil::chunk_of(spawn.0);
+    let spawn_chunk_z = anvil::chunk_of(spawn.2);
+
+    let mut heights: Vec<i64> = vec![];
+    let mut biome_counts: HashMap<String, usize> = HashMap::new();
+    let mut biome_column_count = 0;
+
+    for chunk_x in spawn_chunk_x - SPAWN_RADIUS_CHUNKS..=spawn_chunk_x + SPAWN_RADIUS_CHUNKS {
+        for chunk_z in spawn_chunk_z - SPAWN_RADIUS_CHUNKS..=spawn_chunk_z + SPAWN_RADIUS_CHUNKS {
+            let chunk = match region_reader.read_chunk(chunk_x, chunk_z) {
+                Ok(Some(chunk)) if chunk.is_fully_generated() => chunk,
+                Ok(_) => continue,
+                Err(e) => {
+                    println!("ERROR: Unable to read chunk {},{}: {}", chunk_x, chunk_z, e);
+                    continue;
+                }
+            };
+
+            for x in 0..16 {
+                for z in 0..16 {
+                    let height = match chunk.surface_height(x, z) {
+                        Some(height) => height,
+                        None => continue,
+                    };
+                    heights.push(height);
+
+                    if let Some(biome) = chunk.biome_at(x, height, z) {
+                        *biome_counts.entry(biome).or_insert(0) += 1;
+                        biome_column_count += 1;
+                    }
+                }
+            }
+        }
+    }
+
+    if heights.is_empty() {
+        return Err("No fully generated chunks around spawn".to_string());
+    }
+
+    let column_count = heights.len();
+    let mean_height = heights.iter().sum::<i64>() as f64 / column_count as f64;
+    let variance = heights
+        .iter()
+        .map(|height| (*height as f64 - mean_height).powi(2))
+        .sum::<f64>()
+        / column_count as f64;
+
+    let ocean_coverage = if biome_column_count > 0 {
+        let ocean_column_count: usize = biome_counts
+            .iter()
+            .filter(|(biome, _)| biome.contains("ocean"))
+            .map(|(_, count)| count)
+            .sum();
+        Some(ocean_column_count as f64 / biome_column_count as f64)
+    } else {
+        None
+    };
+
+    let mut surface_biomes: Vec<(String, f64)> = biome_counts
+        .into_iter()
+        .map(|(biome, count)| (biome, count as f64 / biome_column_count as f64))
+        .collect();
+    surface_biomes.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap().then_with(|| a.0.cmp(&b.0)));
+
+    Ok(TerrainSummary {
+        column_count,
+        min_height: *heights.iter().min().unwrap(),
+        max_height: *heights.iter().max().unwrap(),
+        mean_height,
+        height_stddev: variance.sqrt(),
+        ocean_coverage,
+        surface_biomes,
+    })
+}
@@ -0,0 +1,304 @@
+use std::convert::TryFrom;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use ed25519_dalek::{PublicKey, Signature};
+use reqwest::blocking::Client;
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::gatherserver::{GatherServer, CLIENT_VERSION};
+
+// If this exists, it's used instead of asking the gather server about updates
+pub const UPDATE_MANIFEST_PATH: &str = "update_manifest.toml";
+
+// Release builds are made with the public half of the key updates are signed with.
+// Builds made without one can still tell users about updates, but won't install them.
+const UPDATE_PUBLIC_KEY: Option<&str> = option_env!("SPICYGARDEN_UPDATE_PUBLIC_KEY");
+
+// A newer release of the client, as described by the gather server or update_manifest.toml
+#[derive(Debug, Clone, Deserialize)]
+pub struct UpdateInfo {
+    pub version: String,
+    #[serde(default)]
+    pub notes: Option<String>,
+    // The replacement binary for this platform, if there is one
+    #[serde(default)]
+    pub url: Option<String>,
+    #[serde(default)]
+    pub sha256: Option<String>,
+    // Hex-encoded ed25519 signature of the version followed by the binary's raw SHA-256.
+    // Covering the version too means an old release can't be passed off as a new one.
+    #[serde(default)]
+    pub signature: Option<String>,
+}
+
+// Written next to a downloaded update, so it can be checked again before it's run
+#[derive(Serialize, Deserialize)]
+struct PendingUpdate {
+    version: String,
+    signature: String,
+}
+
+// Something like "linux-x86_64", so the gather server can offer the right binary
+fn platform() -> String {
+    format!("{}-{}", env::consts::OS, env::consts::ARCH)
+}
+
+fn parse_version(version: &str) -> Option<Vec<u64>> {
+    version
+        .trim()
+        .trim_start_matches('v')
+        .split('.')
+        .map(|part| part.parse::<u64>().ok())
+        .collect()
+}
+
+fn is_newer(version: &str) -> bool {
+    match (parse_version(version), parse_version(CLIENT_VERSION)) {
+        (Some(available), Some(current)) => available > current,
+        _ => false,
+    }
+}
+
+// Local manifests win, so updates can be tested without touching the gather server
+fn load_update_info(gather_server: &mut GatherServer) -> Result<Option<UpdateInfo>, String> {
+    if Path::new(UPDATE_MANIFEST_PATH).exists() {
+        let toml_slice = fs::read(UPDATE_MANIFEST_PATH)
+            .map_err(|e| format!("Unable to read {}: {}", UPDATE_MANIFEST_PATH, e))?;
+
+        return toml::from_slice::<UpdateInfo>(&toml_slice)
+            .map(Some)
+            .map_err(|e| format!("Failed to parse {}: {}", UPDATE_MANIFEST_PATH, e));
+    }
+
+    let response = gather_server
+        .get("client_update", &[&platform()])
+        .and_then(|request| request.send())
+        .map_err(|e| format!("Unable to check for updates: {}", e))?;
+
+    match response.status() {
+        StatusCode::OK => response
+            .json::<UpdateInfo>()
+            .map(Some)
+            .map_err(|e| format!("Unable to understand gather server's update info: {}", e)),
+        // This gather server doesn't hand out updates
+        StatusCode::NOT_FOUND => Ok(None),
+        status => Err(format!("Gather server responded with {} when asked for updates", status)),
+    }
+}
+
+// Returns the newest release, but only if it's newer than this one
+pub fn check_for_update(address: &str, client_key: &str) -> Result<Option<UpdateInfo>, String> {
+    let update_info = load_update_info(&mut GatherServer::new(address, client_key))?;
+    Ok(update_info.filter(|update_info| is_newer(&update_info.version)))
+}
+
+// Async wrapper function so the GUI doesn't freeze up while checking
+pub async fn check_for_update_async_wrapper(
+    address: String,
+    client_key: String,
+) -> Result<Option<UpdateInfo>, String> {
+    check_for_update(&address, &client_key)
+}
+
+// The executable's path with something tacked onto the end, e.g. SpicyGarden.update
+fn executable_with_suffix(executable: &Path, suffix: &str) -> PathBuf {
+    let mut file_name = executable.file_name().unwrap_or_default().to_os_string();
+    file_name.push(suffix);
+    executable.with_file_name(file_name)
+}
+
+// Checks that the binary is the release it claims to be, and that the release is newer than this one
+fn verify_update(version: &str, binary: &[u8], signature_hex: &str) -> Result<(), String> {
+    if !is_newer(version) {
+        return Err(format!(
+            "Update to {} isn't newer than {}, refusing to install it",
+            version, CLIENT_VERSION
+        ));
+    }
+
+    let mut signed_message = version.as_bytes().to_vec();
+    signed_message.extend_from_slice(&Sha256::digest(binary));
+
+    verify_signature(&signed_message, signature_hex)
+}
+
+fn verify_signature(message: &[u8], signature_hex: &str) -> Result<(), String> {
+    let public_key_hex = UPDATE_PUBLIC_KEY
+        .ok_or("This build of SpicyGarden can't verify updates, so it won't install them")?;
+
+    let public_key = hex::decode(public_key_hex)
+        .ok()
+        .and_then(|bytes| PublicKey::from_bytes(&bytes).ok())
+        .ok_or("This build's update public key is invalid")?;
+
+    let signature = hex::decode(signature_hex)
+        .ok()
+        .and_then(|bytes| Signature::try_from(&bytes[..]).ok())
+        .ok_or("Update signature is malformed")?;
+
+    public_key
+        .verify_strict(message, &signature)
+        .map_err(|_| "Update signature doesn't match, refusing to install it".to_string())
+}
+
+// Downloads and verifies the replacement binary, leaving it next to this one to be
+// swapped in by apply_pending_update the next time SpicyGarden starts
+pub fn download_update(update_info: &UpdateInfo) -> Result<String, String> {
+    let url = update_info
+        .url
+        .as_ref()
+        .ok_or("There's no download for this platform")?;
+    let sha256 = update_info
+        .sha256
+        .as_ref()
+        .ok_or("Update doesn't say what its SHA-256 should be")?;
+    let signature = update_info.signature.as_ref().ok_or("Update isn't signed")?;
+
+    let executable =
+        env::current_exe().map_err(|e| format!("Unable to find SpicyGarden's executable: {}", e))?;
+
+    // Binaries are big, and the default timeout doesn't leave room for slow connections
+    let http_client = Client::builder()
+        .timeout(None)
+        .build()
+        .map_err(|e| format!("Unable to set up downloads: {}", e))?;
+
+    let response = http_client
+        .get(url)
+        .send()
+        .map_err(|e| format!("Unable to download {}: {}", url, e))?;
+
+    if response.status() != StatusCode::OK {
+        return Err(format!("Downloading {} failed: {}", url, response.status()));
+    }
+
+    let binary = response
+        .bytes()
+        .map_err(|e| format!("Unable to download {}: {}", url, e))?;
+
+    if !format!("{:x}", Sha256::digest(&binary)).eq_ignore_ascii_case(sha256) {
+        return Err("Downloaded update doesn't match its SHA-256, refusing to install it".to_string());
+    }
+
+    verify_update(&update_info.version, &binary, signature)?;
+
+    let download_path = executable_with_suffix(&executable, ".update.download");
+    let pending_path = executable_with_suffix(&executable, ".update");
+    let pending_info_path = executable_with_suffix(&executable, ".update.toml");
+
+    let pending_info = toml::to_string(&PendingUpdate {
+        version: update_info.version.clone(),
+        signature: signature.clone(),
+    })
+    .map_err(|e| format!("Failed to serialize update info: {}", e))?;
+
+    fs::write(&pending_info_path, pending_info)
+        .map_err(|e| format!("Unable to write {}: {}", pending_info_path.display(), e))?;
+
+    fs::write(&download_path, &binary)
+        .map_err(|e| format!("Unable to write {}: {}", download_path.display(), e))?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        fs::set_permissions(&download_path, fs::Permissions::from_mode(0o755))
+            .map_err(|e| format!("Unable to make {} executable: {}", download_path.display(), e))?;
+    }
+
+    fs::rename(&download_path, &pending_path)
+        .map_err(|e| format!("Unable to move {} into place: {}", pending_path.display(), e))?;
+
+    Ok(format!(
+        "SpicyGarden {} will be installed the next time it starts",
+        update_info.version
+    ))
+}
+
+// Async wrapper function so the GUI doesn't freeze up while downloading
+pub async fn download_update_async_wrapper(update_info: UpdateInfo) -> Result<String, String> {
+    download_update(&update_info)
+}
+
+// Checks a downloaded update the same way download_update did, since the file
+// could have been swapped out while it sat on disk
+fn verify_pending_update(pending_path: &Path, pending_info_path: &Path) -> Result<(), String> {
+    let toml_slice = fs::read(pending_info_path)
+        .map_err(|e| format!("Unable to read {}: {}", pending_info_path.display(), e))?;
+    let pending_info = toml::from_slice::<PendingUpdate>(&toml_slice)
+        .map_err(|e| format!("Failed to parse {}: {}", pending_info_path.display(), e))?;
+
+    let binary = fs::read(pending_path)
+        .map_err(|e| format!("Unable to read {}: {}", pending_path.display(), e))?;
+
+    verify_update(&pending_info.version, &binary, &pending_info.signature)
+}
+
+// Replaces this process with the updated executable, so there's only ever one SpicyGarden running
+#[cfg(unix)]
+fn run_updated(executable: &Path) -> Option<i32> {
+    use std::os::unix::process::CommandExt;
+
+    // exec only returns if it couldn't start the new executable
+    let e = Command::new(executable).args(env::args_os().skip(1)).exec();
+    println!("ERROR: Unable to start updated SpicyGarden: {:?}", e);
+    None
+}
+
+// Windows can't replace a running process, so the old one waits and passes on the exit code
+#[cfg(not(unix))]
+fn run_updated(executable: &Path) -> Option<i32> {
+    match Command::new(executable).args(env::args_os().skip(1)).status() {
+        Ok(status) => Some(status.code().unwrap_or(1)),
+        Err(e) => {
+            println!("ERROR: Unable to start updated SpicyGarden: {:?}", e);
+            None
+        }
+    }
+}
+
+// Swaps in an update left behind by download_update and hands over to it.
+// On Unix this never returns if the update starts. Elsewhere, it returns the new
+// version's exit code once it's done. Returns None to carry on as we are.
+pub fn apply_pending_update() -> Option<i32> {
+    let executable = env::current_exe().ok()?;
+    let pending_path = executable_with_suffix(&executable, ".update");
+    let pending_info_path = executable_with_suffix(&executable, ".update.toml");
+    let old_path = executable_with_suffix(&executable, ".old");
+
+    // Left behind by the last update, now that it isn't running anymore
+    _ = fs::remove_file(&old_path);
+
+    if !pending_path.exists() {
+        return None;
+    }
+
+    if let Err(e) = verify_pending_update(&pending_path, &pending_info_path) {
+        println!("ERROR: Not installing downloaded update: {}", e);
+        _ = fs::remove_file(&pending_path);
+        _ = fs::remove_file(&pending_info_path);
+        return None;
+    }
+
+    println!("Installing downloaded update...");
+
+    if let Err(e) = fs::rename(&executable, &old_path) {
+        println!("ERROR: Unable to install update: {:?}", e);
+        return None;
+    }
+
+    if let Err(e) = fs::rename(&pending_path, &executable) {
+        println!("ERROR: Unable to install update: {:?}", e);
+        _ = fs::rename(&old_path, &executable);
+        return None;
+    }
+
+    _ = fs::remove_file(&pending_info_path);
+
+    run_updated(&executable)
+}